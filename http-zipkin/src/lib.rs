@@ -13,13 +13,19 @@
 //  limitations under the License.
 
 //! HTTP header propagation for Zipkin trace information.
+//!
+//! If the `tower` Cargo feature is enabled, the `tower` module provides middleware which traces HTTP servers and
+//! clients.
 #![doc(html_root_url = "https://docs.rs/http-zipkin/0.3")]
 #![warn(missing_docs)]
 
-use http::header::{HeaderMap, HeaderValue};
-use std::fmt::Write;
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use std::net::SocketAddr;
 use std::str::FromStr;
-use zipkin::{SamplingFlags, TraceContext};
+use zipkin::{trace_context, Attached, Endpoint, OpenSpan, SamplingFlags, TraceContext};
+
+#[cfg(feature = "tower")]
+pub mod tower;
 
 const X_B3_SAMPLED: &str = "X-B3-Sampled";
 const X_B3_FLAGS: &str = "X-B3-Flags";
@@ -65,14 +71,20 @@ pub fn set_sampling_flags(flags: SamplingFlags, headers: &mut HeaderMap) {
 }
 
 /// Deserializes sampling flags from a set of HTTP headers.
+///
+/// If a `b3` header is present, it takes precedence over the `X-B3-` headers. See `get_trace_context` for how multiple
+/// or comma-folded `b3` values are handled.
 pub fn get_sampling_flags(headers: &HeaderMap) -> SamplingFlags {
-    match headers.get(B3) {
-        Some(value) => get_sampling_flags_single(value),
-        None => get_sampling_flags_multi(headers),
+    if headers.contains_key(B3) {
+        b3_values(headers)
+            .find_map(get_sampling_flags_single)
+            .unwrap_or_default()
+    } else {
+        get_sampling_flags_multi(headers)
     }
 }
 
-fn get_sampling_flags_single(value: &HeaderValue) -> SamplingFlags {
+fn get_sampling_flags_single(value: &str) -> Option<SamplingFlags> {
     let mut builder = SamplingFlags::builder();
 
     if value == "d" {
@@ -81,20 +93,19 @@ fn get_sampling_flags_single(value: &HeaderValue) -> SamplingFlags {
         builder.sampled(true);
     } else if value == "0" {
         builder.sampled(false);
-    } else if let Some(context) = get_trace_context_single(value) {
-        return context.sampling_flags();
+    } else {
+        return get_trace_context_single(value).map(|c| c.sampling_flags());
     }
 
-    builder.build()
+    Some(builder.build())
 }
 
 fn get_sampling_flags_multi(headers: &HeaderMap) -> SamplingFlags {
     let mut builder = SamplingFlags::builder();
 
-    if let Some(flags) = headers.get(X_B3_FLAGS) {
-        if flags == "1" {
-            builder.debug(true);
-        }
+    // debug takes precedence over (and implies) sampling, and any value of X-B3-Flags other than 1 is ignored
+    if matches!(headers.get(X_B3_FLAGS), Some(flags) if flags == "1") {
+        builder.debug(true);
     } else if let Some(sampled) = headers.get(X_B3_SAMPLED) {
         if sampled == "1" {
             builder.sampled(true);
@@ -109,20 +120,11 @@ fn get_sampling_flags_multi(headers: &HeaderMap) -> SamplingFlags {
 /// Serializes a trace context into the `b3` header.
 ///
 /// This form is more compact than the old `X-B3-` set of headers, but some implementations may not support it.
+///
+/// Per the B3 specification, the sampling state of a debug context is written as `d`, which implies that the context
+/// is sampled. The parent span ID, if present, always follows the sampling state.
 pub fn set_trace_context_single(context: TraceContext, headers: &mut HeaderMap) {
-    let mut value = String::new();
-    write!(value, "{}-{}", context.trace_id(), context.span_id()).unwrap();
-    if context.debug() {
-        value.push_str("-d");
-    } else if context.sampled() == Some(true) {
-        value.push_str("-1");
-    } else if context.sampled() == Some(false) {
-        value.push_str("-0");
-    }
-    if let Some(parent_id) = context.parent_id() {
-        write!(value, "-{}", parent_id).unwrap();
-    }
-    headers.insert(B3, HeaderValue::from_str(&value).unwrap());
+    headers.insert(B3, HeaderValue::from_str(&context.to_string()).unwrap());
 }
 
 /// Serializes a trace context into a set of HTTP headers.
@@ -150,46 +152,85 @@ pub fn set_trace_context(context: TraceContext, headers: &mut HeaderMap) {
     );
 }
 
-/// Deserializes a trace context from a set of HTTP headers.
-pub fn get_trace_context(headers: &HeaderMap) -> Option<TraceContext> {
-    match headers.get(B3) {
-        Some(value) => get_trace_context_single(value),
-        None => get_trace_context_multi(headers),
+/// The set of HTTP headers representing a trace context.
+///
+/// The headers are the same as those written by `set_trace_context`. This is useful when building requests with
+/// APIs that take headers by value rather than a mutable `HeaderMap`.
+#[derive(Debug, Clone)]
+pub struct TraceContextHeaders(HeaderMap);
+
+impl From<TraceContext> for TraceContextHeaders {
+    fn from(context: TraceContext) -> TraceContextHeaders {
+        let mut headers = HeaderMap::new();
+        set_trace_context(context, &mut headers);
+        TraceContextHeaders(headers)
     }
 }
 
-fn get_trace_context_single(value: &HeaderValue) -> Option<TraceContext> {
-    let mut parts = value.to_str().ok()?.split('-');
+impl TraceContextHeaders {
+    /// Returns the headers as a `HeaderMap`.
+    pub fn into_headers(self) -> HeaderMap {
+        self.0
+    }
+}
 
-    let trace_id = parts.next()?.parse().ok()?;
-    let span_id = parts.next()?.parse().ok()?;
+impl IntoIterator for TraceContextHeaders {
+    type Item = (HeaderName, HeaderValue);
+    type IntoIter = TraceContextHeadersIter;
 
-    let mut builder = TraceContext::builder();
-    builder.trace_id(trace_id).span_id(span_id);
+    fn into_iter(self) -> TraceContextHeadersIter {
+        TraceContextHeadersIter(self.0.into_iter())
+    }
+}
 
-    let maybe_sampling = match parts.next() {
-        Some(next) => next,
-        None => return Some(builder.build()),
-    };
+/// An iterator over the headers in a `TraceContextHeaders`.
+#[derive(Debug)]
+pub struct TraceContextHeadersIter(header::IntoIter<HeaderValue>);
 
-    let parent_id = if maybe_sampling == "d" {
-        builder.debug(true);
-        parts.next()
-    } else if maybe_sampling == "1" {
-        builder.sampled(true);
-        parts.next()
-    } else if maybe_sampling == "0" {
-        builder.sampled(false);
-        parts.next()
-    } else {
-        Some(maybe_sampling)
-    };
+impl Iterator for TraceContextHeadersIter {
+    type Item = (HeaderName, HeaderValue);
 
-    if let Some(parent_id) = parent_id {
-        builder.parent_id(parent_id.parse().ok()?);
+    fn next(&mut self) -> Option<(HeaderName, HeaderValue)> {
+        // each header only has a single value, so the name is always present
+        self.0
+            .next()
+            .map(|(name, value)| (name.expect("header name missing"), value))
     }
 
-    Some(builder.build())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Deserializes a trace context from a set of HTTP headers.
+///
+/// The width of the returned context's trace ID matches that of the header: a 16 character ID produces an 8 byte
+/// `TraceId`, and a 32 character ID produces a 16 byte `TraceId`. The two forms of a trace ID do not compare equal, so
+/// if peers may propagate the trace with a different width than it originated with, use `normalize_trace_id` to
+/// convert the context to a consistent width.
+///
+/// If a `b3` header is present, it takes precedence over the `X-B3-` headers. Some proxies fold multiple headers into
+/// a single comma-separated value, so each `b3` header is split on commas, and the first well-formed value in header
+/// order is used.
+pub fn get_trace_context(headers: &HeaderMap) -> Option<TraceContext> {
+    if headers.contains_key(B3) {
+        b3_values(headers).find_map(get_trace_context_single)
+    } else {
+        get_trace_context_multi(headers)
+    }
+}
+
+fn b3_values(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(B3)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+}
+
+fn get_trace_context_single(value: &str) -> Option<TraceContext> {
+    value.parse().ok()
 }
 
 fn get_trace_context_multi(headers: &HeaderMap) -> Option<TraceContext> {
@@ -209,6 +250,77 @@ fn get_trace_context_multi(headers: &HeaderMap) -> Option<TraceContext> {
     Some(builder.build())
 }
 
+/// The width of a trace ID.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TraceIdWidth {
+    /// An 8 byte trace ID.
+    Short,
+    /// A 16 byte trace ID.
+    Long,
+}
+
+/// Converts the trace ID of a context to the specified width.
+///
+/// 16 byte trace IDs are truncated to their lower 8 bytes, and 8 byte trace IDs are extended with upper bytes of
+/// zero.
+pub fn normalize_trace_id(context: TraceContext, width: TraceIdWidth) -> TraceContext {
+    let trace_id = match width {
+        TraceIdWidth::Short => context.trace_id().to_short(),
+        TraceIdWidth::Long => context.trace_id().to_long(),
+    };
+
+    trace_context::Builder::from(context)
+        .trace_id(trace_id)
+        .build()
+}
+
+/// Continues a trace from a set of HTTP headers.
+///
+/// If the headers contain a trace context, the trace is joined. Otherwise, a new trace is started.
+pub fn continue_trace(headers: &HeaderMap) -> OpenSpan<Attached> {
+    match get_trace_context(headers) {
+        Some(context) => zipkin::join_trace(context),
+        None => zipkin::new_trace(),
+    }
+}
+
+/// Continues a trace from a set of HTTP headers, recording the peer that sent them as the span's remote endpoint.
+///
+/// This is equivalent to `continue_trace(headers).with_remote_endpoint(peer_endpoint(peer))`.
+pub fn continue_trace_from_peer(headers: &HeaderMap, peer: SocketAddr) -> OpenSpan<Attached> {
+    continue_trace(headers).with_remote_endpoint(peer_endpoint(peer))
+}
+
+/// Continues a trace from a set of HTTP headers, recording the caller's service name as the span's remote endpoint.
+///
+/// The service name is read from the `header` header, which the caller is expected to set to its own name. If the
+/// header is absent, the span has no remote endpoint. Recording the caller lets Zipkin attribute the call in its
+/// dependency graph even when the caller's own spans are not reported.
+pub fn continue_trace_from_caller(headers: &HeaderMap, header: &HeaderName) -> OpenSpan<Attached> {
+    let mut span = continue_trace(headers);
+    if let Some(endpoint) = caller_endpoint(headers, header) {
+        span.remote_endpoint(endpoint);
+    }
+    span
+}
+
+/// Creates an endpoint representing a caller from the service name in the `header` header.
+///
+/// Returns `None` if the header is absent, empty, or not a valid string.
+pub fn caller_endpoint(headers: &HeaderMap, header: &HeaderName) -> Option<Endpoint> {
+    let service_name = headers.get(header)?.to_str().ok()?.trim();
+    if service_name.is_empty() {
+        return None;
+    }
+
+    Some(Endpoint::builder().service_name(service_name).build())
+}
+
+/// Creates an endpoint representing a peer from its socket address.
+pub fn peer_endpoint(peer: SocketAddr) -> Endpoint {
+    Endpoint::from_socket_addr(peer)
+}
+
 fn parse_header<T>(headers: &HeaderMap, name: &str) -> Option<T>
 where
     T: FromStr,
@@ -325,6 +437,58 @@ mod test {
         assert_eq!(get_sampling_flags(&headers), flags);
     }
 
+    #[test]
+    fn flags_debug_and_sampled() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-Flags", HeaderValue::from_static("1"));
+        headers.insert("X-B3-Sampled", HeaderValue::from_static("1"));
+
+        let flags = get_sampling_flags(&headers);
+        assert!(flags.debug());
+        assert_eq!(flags.sampled(), Some(true));
+    }
+
+    #[test]
+    fn flags_debug_and_unsampled() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-Flags", HeaderValue::from_static("1"));
+        headers.insert("X-B3-Sampled", HeaderValue::from_static("0"));
+
+        let flags = get_sampling_flags(&headers);
+        assert!(flags.debug());
+        assert_eq!(flags.sampled(), Some(true));
+    }
+
+    #[test]
+    fn flags_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-Flags", HeaderValue::from_static("0"));
+
+        assert_eq!(get_sampling_flags(&headers), SamplingFlags::default());
+    }
+
+    #[test]
+    fn flags_invalid_and_sampled() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-Flags", HeaderValue::from_static("0"));
+        headers.insert("X-B3-Sampled", HeaderValue::from_static("1"));
+
+        let flags = get_sampling_flags(&headers);
+        assert!(!flags.debug());
+        assert_eq!(flags.sampled(), Some(true));
+    }
+
+    #[test]
+    fn flags_invalid_and_unsampled() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-Flags", HeaderValue::from_static("0"));
+        headers.insert("X-B3-Sampled", HeaderValue::from_static("0"));
+
+        let flags = get_sampling_flags(&headers);
+        assert!(!flags.debug());
+        assert_eq!(flags.sampled(), Some(false));
+    }
+
     #[test]
     fn trace_context() {
         let mut headers = HeaderMap::new();
@@ -370,6 +534,29 @@ mod test {
         assert_eq!(get_trace_context(&headers), Some(context));
     }
 
+    #[test]
+    fn trace_context_debug_single() {
+        let mut headers = HeaderMap::new();
+        let context = TraceContext::builder()
+            .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+            .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .debug(true)
+            .build();
+        set_trace_context_single(context, &mut headers);
+
+        let mut expected_headers = HeaderMap::new();
+        expected_headers.insert(
+            "b3",
+            HeaderValue::from_static("0001020304050607-0203040506070809-d-0102030405060708"),
+        );
+        assert_eq!(headers, expected_headers);
+
+        assert_eq!(get_trace_context(&headers), Some(context));
+        assert!(get_sampling_flags(&headers).debug());
+        assert_eq!(get_sampling_flags(&headers).sampled(), Some(true));
+    }
+
     #[test]
     fn trace_context_unsampled_single() {
         let mut headers = HeaderMap::new();
@@ -428,4 +615,218 @@ mod test {
 
         assert_eq!(get_trace_context(&headers), Some(context));
     }
+
+    #[test]
+    fn continue_trace_joined() {
+        let mut headers = HeaderMap::new();
+        let context = TraceContext::builder()
+            .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+            .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+        set_trace_context(context, &mut headers);
+
+        let span = continue_trace(&headers);
+        assert_eq!(span.context(), context);
+    }
+
+    #[test]
+    fn continue_trace_new() {
+        let headers = HeaderMap::new();
+
+        let span = continue_trace(&headers);
+        assert_eq!(span.context().parent_id(), None);
+        assert_eq!(
+            span.context().trace_id().bytes(),
+            span.context().span_id().bytes()
+        );
+    }
+
+    #[test]
+    fn peer_endpoint_ipv4() {
+        let endpoint = peer_endpoint("127.0.0.1:8080".parse().unwrap());
+        assert_eq!(endpoint.service_name(), None);
+        assert_eq!(endpoint.ipv4(), Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(endpoint.ipv6(), None);
+        assert_eq!(endpoint.port(), Some(8080));
+    }
+
+    #[test]
+    fn peer_endpoint_ipv6() {
+        let endpoint = peer_endpoint("[::1]:8080".parse().unwrap());
+        assert_eq!(endpoint.service_name(), None);
+        assert_eq!(endpoint.ipv4(), None);
+        assert_eq!(endpoint.ipv6(), Some("::1".parse().unwrap()));
+        assert_eq!(endpoint.port(), Some(8080));
+    }
+
+    #[test]
+    fn caller_endpoint_present() {
+        let header = HeaderName::from_static("x-caller-service");
+        let mut headers = HeaderMap::new();
+        headers.insert(&header, HeaderValue::from_static("frontend"));
+
+        let endpoint = caller_endpoint(&headers, &header).unwrap();
+        assert_eq!(endpoint.service_name(), Some("frontend"));
+        assert_eq!(endpoint.ipv4(), None);
+        assert_eq!(endpoint.port(), None);
+    }
+
+    #[test]
+    fn caller_endpoint_absent() {
+        let header = HeaderName::from_static("x-caller-service");
+        let mut headers = HeaderMap::new();
+        assert!(caller_endpoint(&headers, &header).is_none());
+
+        headers.insert(&header, HeaderValue::from_static(""));
+        assert!(caller_endpoint(&headers, &header).is_none());
+    }
+
+    #[test]
+    fn trace_context_headers() {
+        let context = TraceContext::builder()
+            .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+            .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+        let mut expected_headers = HeaderMap::new();
+        set_trace_context(context, &mut expected_headers);
+
+        let headers = TraceContextHeaders::from(context);
+        assert_eq!(headers.clone().into_headers(), expected_headers);
+
+        let headers = headers.into_iter().collect::<HeaderMap>();
+        assert_eq!(headers, expected_headers);
+    }
+
+    #[test]
+    fn trace_id_width_follows_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-B3-TraceId", HeaderValue::from_static("0001020304050607"));
+        headers.insert("X-B3-SpanId", HeaderValue::from_static("0203040506070809"));
+        let context = get_trace_context(&headers).unwrap();
+        assert_eq!(context.trace_id().bytes().len(), 8);
+
+        headers.insert(
+            "X-B3-TraceId",
+            HeaderValue::from_static("00000000000000000001020304050607"),
+        );
+        let long_context = get_trace_context(&headers).unwrap();
+        assert_eq!(long_context.trace_id().bytes().len(), 16);
+        assert_ne!(long_context, context);
+
+        assert_eq!(
+            normalize_trace_id(long_context, TraceIdWidth::Short),
+            context
+        );
+        assert_eq!(
+            normalize_trace_id(context, TraceIdWidth::Long),
+            long_context
+        );
+        assert_eq!(normalize_trace_id(context, TraceIdWidth::Short), context);
+    }
+
+    #[test]
+    fn normalize_trace_id_truncates_upper_bytes() {
+        let context = TraceContext::builder()
+            .trace_id([1, 2, 3, 4, 5, 6, 7, 8, 0, 1, 2, 3, 4, 5, 6, 7].into())
+            .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+
+        let short = normalize_trace_id(context, TraceIdWidth::Short);
+        assert_eq!(short.trace_id(), [0, 1, 2, 3, 4, 5, 6, 7].into());
+        assert_eq!(short.parent_id(), context.parent_id());
+        assert_eq!(short.span_id(), context.span_id());
+        assert_eq!(short.sampling_flags(), context.sampling_flags());
+    }
+
+    #[test]
+    fn trace_context_folded_b3() {
+        let context = TraceContext::builder()
+            .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "b3",
+            HeaderValue::from_static(
+                "0001020304050607-0203040506070809-1, 0706050403020100-0908070605040302-0",
+            ),
+        );
+        assert_eq!(get_trace_context(&headers), Some(context));
+        assert_eq!(get_sampling_flags(&headers), context.sampling_flags());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "b3",
+            HeaderValue::from_static("garbage,0001020304050607-0203040506070809-1"),
+        );
+        assert_eq!(get_trace_context(&headers), Some(context));
+        assert_eq!(get_sampling_flags(&headers), context.sampling_flags());
+    }
+
+    #[test]
+    fn trace_context_multiple_b3() {
+        let context = TraceContext::builder()
+            .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+            .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+
+        let mut headers = HeaderMap::new();
+        headers.append("b3", HeaderValue::from_static("not-a-context"));
+        headers.append(
+            "b3",
+            HeaderValue::from_static("0001020304050607-0203040506070809-1"),
+        );
+        headers.append(
+            "b3",
+            HeaderValue::from_static("0706050403020100-0908070605040302-0"),
+        );
+        assert_eq!(get_trace_context(&headers), Some(context));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("b3", HeaderValue::from_static("not-a-context"));
+        headers.insert("X-B3-TraceId", HeaderValue::from_static("0001020304050607"));
+        headers.insert("X-B3-SpanId", HeaderValue::from_static("0203040506070809"));
+        assert_eq!(get_trace_context(&headers), None);
+    }
+
+    #[test]
+    fn trace_context_long_uppercase_trace_id() {
+        let context = TraceContext::builder()
+            .trace_id([0xa0, 0xb1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15].into())
+            .span_id([0xc2, 3, 4, 5, 6, 7, 8, 9].into())
+            .sampled(true)
+            .build();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-B3-TraceId",
+            HeaderValue::from_static("A0B102030405060708090A0B0C0D0E0F"),
+        );
+        headers.insert("X-B3-SpanId", HeaderValue::from_static("C203040506070809"));
+        headers.insert("X-B3-Sampled", HeaderValue::from_static("1"));
+        assert_eq!(get_trace_context(&headers), Some(context));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "b3",
+            HeaderValue::from_static("A0B102030405060708090A0B0C0D0E0F-C203040506070809-1"),
+        );
+        assert_eq!(get_trace_context(&headers), Some(context));
+
+        // the span ID stays 64 bits when the trace ID is 128 bits, and IDs are always written in lowercase
+        let mut headers = HeaderMap::new();
+        set_trace_context(context, &mut headers);
+        assert_eq!(headers["X-B3-TraceId"], "a0b102030405060708090a0b0c0d0e0f");
+        assert_eq!(headers["X-B3-SpanId"], "c203040506070809");
+        assert_eq!(get_trace_context(&headers), Some(context));
+    }
 }
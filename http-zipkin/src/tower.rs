@@ -0,0 +1,187 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! Tower middleware for tracing HTTP servers and clients.
+//!
+//! Requires the `tower` Cargo feature.
+use http::{Request, Response};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+use zipkin::{Detached, Kind, OpenSpan, TagKey};
+
+/// A layer which wraps services in `Server`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ServerLayer(());
+
+impl ServerLayer {
+    /// Creates a new `ServerLayer`.
+    pub fn new() -> ServerLayer {
+        ServerLayer(())
+    }
+}
+
+impl<S> Layer<S> for ServerLayer {
+    type Service = Server<S>;
+
+    fn layer(&self, inner: S) -> Server<S> {
+        Server::new(inner)
+    }
+}
+
+/// A service which traces the requests handled by an HTTP server.
+///
+/// Each request is handled in a server span named after the request's method, in lowercase. If the request's headers
+/// contain a trace context, the span joins that trace. Otherwise, it starts a new trace. The span is tagged with the
+/// method, path, and response status code of the request, and is finished when the response is returned.
+#[derive(Debug, Clone)]
+pub struct Server<S> {
+    inner: S,
+}
+
+impl<S> Server<S> {
+    /// Creates a new `Server` wrapping another service.
+    pub fn new(inner: S) -> Server<S> {
+        Server { inner }
+    }
+}
+
+impl<S, B1, B2> Service<Request<B1>> for Server<S>
+where
+    S: Service<Request<B1>, Response = Response<B2>>,
+{
+    type Response = Response<B2>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B1>) -> ResponseFuture<S::Future> {
+        let span = crate::continue_trace(req.headers()).with_kind(Kind::Server);
+        let span = tag_request(span, &req);
+        let future = self.inner.call(req);
+
+        ResponseFuture {
+            span: Some(span.detach()),
+            future,
+        }
+    }
+}
+
+/// A layer which wraps services in `Client`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ClientLayer(());
+
+impl ClientLayer {
+    /// Creates a new `ClientLayer`.
+    pub fn new() -> ClientLayer {
+        ClientLayer(())
+    }
+}
+
+impl<S> Layer<S> for ClientLayer {
+    type Service = Client<S>;
+
+    fn layer(&self, inner: S) -> Client<S> {
+        Client::new(inner)
+    }
+}
+
+/// A service which traces the requests made by an HTTP client.
+///
+/// Each request is made in a client span named after the request's method, in lowercase, which is a child of the
+/// current span. The span's context is propagated to the server in the request's headers. The span is tagged with the
+/// method, path, and response status code of the request, and is finished when the response is received.
+#[derive(Debug, Clone)]
+pub struct Client<S> {
+    inner: S,
+}
+
+impl<S> Client<S> {
+    /// Creates a new `Client` wrapping another service.
+    pub fn new(inner: S) -> Client<S> {
+        Client { inner }
+    }
+}
+
+impl<S, B1, B2> Service<Request<B1>> for Client<S>
+where
+    S: Service<Request<B1>, Response = Response<B2>>,
+{
+    type Response = Response<B2>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B1>) -> ResponseFuture<S::Future> {
+        let span = zipkin::next_span().with_kind(Kind::Client);
+        let span = tag_request(span, &req);
+        crate::set_trace_context(span.context(), req.headers_mut());
+        let future = self.inner.call(req);
+
+        ResponseFuture {
+            span: Some(span.detach()),
+            future,
+        }
+    }
+}
+
+fn tag_request<T, B>(span: OpenSpan<T>, req: &Request<B>) -> OpenSpan<T> {
+    span.with_name(req.method().as_str().to_ascii_lowercase())
+        .with_http_request(req.method().as_str(), req.uri().path())
+}
+
+pin_project! {
+    /// The future returned by `Server` and `Client`.
+    #[derive(Debug)]
+    pub struct ResponseFuture<F> {
+        span: Option<OpenSpan<Detached>>,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let span = this.span.as_mut().expect("future polled after completion");
+        let _guard = zipkin::set_current(span.context());
+
+        let r = match this.future.poll(cx) {
+            Poll::Ready(r) => r,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match &r {
+            Ok(response) => span.http_response(response.status().as_u16()),
+            Err(_) => span.tag(TagKey::ERROR, "request failed"),
+        }
+        *this.span = None;
+
+        Poll::Ready(r)
+    }
+}
@@ -0,0 +1,143 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::Mutex;
+use zipkin::sample::AlwaysSampler;
+use zipkin::{Endpoint, Report, Span, TraceId};
+
+static SPANS: Mutex<Vec<Span>> = Mutex::new(vec![]);
+
+struct TestReporter;
+
+impl Report for TestReporter {
+    fn report(&self, span: Span) {
+        SPANS.lock().unwrap().push(span);
+    }
+}
+
+fn init() {
+    let _ = zipkin::set_tracer(AlwaysSampler, TestReporter, Endpoint::builder().build());
+}
+
+fn take(trace_id: TraceId) -> Vec<Span> {
+    let mut spans = SPANS.lock().unwrap();
+    let (matching, other) = spans.drain(..).partition(|s| s.trace_id() == trace_id);
+    *spans = other;
+    matching
+}
+
+fn b3(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("b3", HeaderValue::from_static(value));
+    headers
+}
+
+#[test]
+fn unsampled_single_header() {
+    init();
+
+    let headers = b3("0001020304050607-0203040506070809-0");
+    let context = http_zipkin::get_trace_context(&headers).unwrap();
+    assert_eq!(context.sampled(), Some(false));
+    assert!(!context.debug());
+
+    let span = zipkin::join_trace(context);
+    assert!(!span.is_recording());
+    assert_eq!(span.context(), context);
+    drop(span);
+
+    let span = http_zipkin::continue_trace(&headers);
+    assert!(!span.is_recording());
+    let child = zipkin::next_span();
+    assert!(!child.is_recording());
+    assert_eq!(child.context().sampled(), Some(false));
+    drop(child);
+    drop(span);
+
+    assert!(take(context.trace_id()).is_empty());
+}
+
+#[test]
+fn sampled_single_header() {
+    init();
+
+    let headers = b3("0101020304050607-0203040506070809-1");
+    let context = http_zipkin::get_trace_context(&headers).unwrap();
+    assert_eq!(context.sampled(), Some(true));
+
+    let span = http_zipkin::continue_trace(&headers);
+    assert!(span.is_recording());
+    drop(span);
+
+    assert_eq!(take(context.trace_id()).len(), 1);
+}
+
+#[test]
+fn deferred_single_header() {
+    init();
+
+    let headers = b3("0201020304050607-0203040506070809");
+    let context = http_zipkin::get_trace_context(&headers).unwrap();
+    assert_eq!(context.sampled(), None);
+
+    // the decision is left to the tracer's sampler
+    let span = http_zipkin::continue_trace(&headers);
+    assert!(span.is_recording());
+    assert_eq!(span.context().sampled(), Some(true));
+    drop(span);
+
+    assert_eq!(take(context.trace_id()).len(), 1);
+}
+
+#[test]
+fn continue_trace_from_caller() {
+    init();
+
+    let header = HeaderName::from_static("x-caller-service");
+    let mut headers = b3("0301020304050607-0203040506070809-1");
+    headers.insert(&header, HeaderValue::from_static("frontend"));
+    let context = http_zipkin::continue_trace_from_caller(&headers, &header).context();
+
+    let headers = b3("0301020304050607-0304050607080910-1");
+    let other = http_zipkin::continue_trace_from_caller(&headers, &header).context();
+
+    let spans = take(context.trace_id());
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(
+        spans[0].remote_endpoint().and_then(|e| e.service_name()),
+        Some("frontend")
+    );
+    assert_eq!(spans[1].id(), other.span_id());
+    assert!(spans[1].remote_endpoint().is_none());
+}
+
+#[test]
+fn continue_trace_from_peer() {
+    init();
+
+    let peer = "127.0.0.1:8080".parse().unwrap();
+    let headers = b3("0401020304050607-0203040506070809-1");
+    let context = http_zipkin::continue_trace_from_peer(&headers, peer).context();
+    assert_eq!(context.trace_id(), TraceId::from([4, 1, 2, 3, 4, 5, 6, 7]));
+    assert_eq!(context.span_id(), [2, 3, 4, 5, 6, 7, 8, 9].into());
+
+    let spans = take(context.trace_id());
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(
+        spans[0].remote_endpoint().and_then(|e| e.socket_addr()),
+        Some(peer)
+    );
+}
@@ -0,0 +1,161 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use futures::executor;
+use futures::future::{self, Ready};
+use http::{Request, Response, StatusCode};
+use http_zipkin::tower::{ClientLayer, ServerLayer};
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+use zipkin::sample::AlwaysSampler;
+use zipkin::{Endpoint, Kind, Report, Span, TagKey, TraceContext};
+
+static SPANS: Mutex<Vec<Span>> = Mutex::new(vec![]);
+
+struct TestReporter;
+
+impl Report for TestReporter {
+    fn report(&self, span: Span) {
+        SPANS.lock().unwrap().push(span);
+    }
+}
+
+fn init() {
+    let _ = zipkin::set_tracer(AlwaysSampler, TestReporter, Endpoint::builder().build());
+}
+
+fn take(trace_id: zipkin::TraceId) -> Vec<Span> {
+    let mut spans = SPANS.lock().unwrap();
+    let (matching, other) = spans.drain(..).partition(|s| s.trace_id() == trace_id);
+    *spans = other;
+    matching
+}
+
+// Responds with the given status, recording the current context and request headers.
+struct TestService {
+    status: StatusCode,
+    context: Option<TraceContext>,
+    headers: http::HeaderMap,
+}
+
+impl TestService {
+    fn new(status: StatusCode) -> TestService {
+        TestService {
+            status,
+            context: None,
+            headers: http::HeaderMap::new(),
+        }
+    }
+}
+
+impl Service<Request<()>> for &mut TestService {
+    type Response = Response<()>;
+    type Error = Infallible;
+    type Future = Ready<Result<Response<()>, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<()>) -> Self::Future {
+        self.context = zipkin::current();
+        self.headers = req.headers().clone();
+        let mut response = Response::new(());
+        *response.status_mut() = self.status;
+        future::ready(Ok(response))
+    }
+}
+
+#[test]
+fn server_joins_trace() {
+    init();
+
+    let context = TraceContext::builder()
+        .trace_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .sampled(true)
+        .build();
+    let mut request = Request::get("/foo/bar").body(()).unwrap();
+    http_zipkin::set_trace_context(context, request.headers_mut());
+
+    let mut inner = TestService::new(StatusCode::OK);
+    let mut service = ServerLayer::new().layer(&mut inner);
+    let response = executor::block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(inner.context, Some(context));
+
+    let spans = take(context.trace_id());
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(spans[0].name(), Some("get"));
+    assert!(matches!(spans[0].kind(), Some(Kind::Server)));
+    assert!(spans[0].shared());
+    assert_eq!(spans[0].tags()["http.method"], "GET");
+    assert_eq!(spans[0].tags()["http.path"], "/foo/bar");
+    assert_eq!(spans[0].tags()["http.status_code"], "200");
+    assert!(!spans[0].tags().contains_key("error"));
+}
+
+#[test]
+fn server_new_trace() {
+    init();
+
+    let request = Request::post("/foo").body(()).unwrap();
+
+    let mut inner = TestService::new(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut service = ServerLayer::new().layer(&mut inner);
+    executor::block_on(service.call(request)).unwrap();
+    let context = inner.context.unwrap();
+    assert_eq!(context.parent_id(), None);
+
+    let spans = take(context.trace_id());
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(spans[0].name(), Some("post"));
+    assert!(!spans[0].shared());
+    assert_eq!(spans[0].tags()["http.status_code"], "500");
+    assert_eq!(spans[0].tags()["error"], "500");
+    for key in spans[0].tags().keys() {
+        assert!(TagKey::ALL.iter().any(|k| k.as_str() == key));
+    }
+}
+
+#[test]
+fn client_propagates_trace() {
+    init();
+
+    let root = zipkin::new_trace();
+    let root_context = root.context();
+
+    let request = Request::put("/foo").body(()).unwrap();
+    let mut inner = TestService::new(StatusCode::NO_CONTENT);
+    let mut service = ClientLayer::new().layer(&mut inner);
+    executor::block_on(service.call(request)).unwrap();
+    drop(root);
+
+    let context = http_zipkin::get_trace_context(&inner.headers).unwrap();
+    assert_eq!(context.trace_id(), root_context.trace_id());
+    assert_eq!(context.parent_id(), Some(root_context.span_id()));
+    assert_eq!(inner.context, Some(context));
+
+    let spans = take(root_context.trace_id());
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(spans[0].name(), Some("put"));
+    assert!(matches!(spans[0].kind(), Some(Kind::Client)));
+    assert_eq!(spans[0].tags()["http.status_code"], "204");
+    assert_eq!(spans[1].id(), root_context.span_id());
+}
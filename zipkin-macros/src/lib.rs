@@ -28,6 +28,15 @@ use syn::{parse_macro_input, Error, Expr, ImplItemFn, Lit, LitStr, Meta, Stmt, T
 /// Both normal and `async` methods and functions are supported. The name of the span is specified as an argument
 /// to the macro attribute.
 ///
+/// Only the function's body is rewritten, so generic parameters, lifetimes, and where clauses are preserved. This
+/// includes methods in trait implementations. The usual caveats apply to `async` trait methods: they require Rust 1.75,
+/// and code that is generic over the trait can't assume their futures are `Send`.
+///
+/// By default, the span is a child of the current span. The `join` option takes an expression evaluating to an
+/// `Option<TraceContext>`, typically computed from the function's arguments. If it evaluates to `Some`, the span
+/// joins that trace, as with `zipkin::join_trace`, rather than creating a child of the current span. This is useful
+/// for instrumenting the entry points of servers.
+///
 /// Requires the `macros` Cargo feature.
 ///
 /// # Examples
@@ -56,6 +65,11 @@ use syn::{parse_macro_input, Error, Expr, ImplItemFn, Lit, LitStr, Meta, Stmt, T
 ///          // ...
 ///     }
 /// }
+///
+/// #[zipkin::spanned(name = "handle yak request", join = http_zipkin::get_trace_context(request.headers()))]
+/// fn handle_yak_request(request: &Request<Yak>) {
+///     // ...
+/// }
 /// ```
 #[proc_macro_attribute]
 pub fn spanned(args: TokenStream, item: TokenStream) -> TokenStream {
@@ -68,11 +82,21 @@ pub fn spanned(args: TokenStream, item: TokenStream) -> TokenStream {
 fn spanned_impl(options: Options, mut func: ImplItemFn) -> Result<TokenStream, Error> {
     let name = &options.name;
 
+    let span = match &options.join {
+        Some(join) => quote! {
+            match #join {
+                Some(__macro_impl_context) => zipkin::join_trace(__macro_impl_context),
+                None => zipkin::next_span(),
+            }
+        },
+        None => quote!(zipkin::next_span()),
+    };
+
     if func.sig.asyncness.is_some() {
         let stmts = &func.block.stmts;
         func.block.stmts = vec![
             syn::parse2(quote! {
-                let __macro_impl_span = zipkin::next_span()
+                let __macro_impl_span = #span
                     .with_name(#name)
                     .detach();
             })
@@ -87,7 +111,7 @@ fn spanned_impl(options: Options, mut func: ImplItemFn) -> Result<TokenStream, E
         ];
     } else {
         let stmt = quote! {
-            let __macro_impl_span = zipkin::next_span().with_name(#name);
+            let __macro_impl_span = #span.with_name(#name);
         };
         func.block.stmts.insert(0, syn::parse2(stmt).unwrap());
     };
@@ -97,6 +121,7 @@ fn spanned_impl(options: Options, mut func: ImplItemFn) -> Result<TokenStream, E
 
 struct Options {
     name: LitStr,
+    join: Option<Expr>,
 }
 
 impl Parse for Options {
@@ -104,6 +129,7 @@ impl Parse for Options {
         let args = Punctuated::<Meta, Token![,]>::parse_terminated(input)?;
 
         let mut name = None;
+        let mut join = None;
 
         for arg in args {
             let meta = match arg {
@@ -119,6 +145,8 @@ impl Parse for Options {
                     },
                     _ => return Err(Error::new_spanned(meta, "expected `name = \"...\"`")),
                 }
+            } else if meta.path.is_ident("join") {
+                join = Some(meta.value);
             } else {
                 return Err(Error::new_spanned(meta.path, "unknown option"));
             }
@@ -126,6 +154,7 @@ impl Parse for Options {
 
         Ok(Options {
             name: name.ok_or_else(|| Error::new(Span::call_site(), "missing `name` option"))?,
+            join,
         })
     }
 }
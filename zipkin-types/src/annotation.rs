@@ -13,7 +13,7 @@
 //  limitations under the License.
 
 //! Annotations.
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 /// Associates an event that explains latency with a timestamp.
 ///
@@ -21,7 +21,9 @@ use std::time::SystemTime;
 ///
 /// Zipkin v1 core annotations such as "cs" and "sr" have been replaced with
 /// `Span::kind`, which interprets timestamp and duration.
-#[derive(Debug, Clone)]
+///
+/// Annotations are ordered by timestamp, and then by value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 pub struct Annotation {
@@ -40,6 +42,12 @@ impl Annotation {
         }
     }
 
+    /// Creates a new `Annotation` at an offset from a base time.
+    #[inline]
+    pub fn at_offset(base: SystemTime, offset: Duration, value: &str) -> Annotation {
+        Annotation::new(base + offset, value)
+    }
+
     /// Creates a new `Annotation` at the current time.
     #[inline]
     pub fn now(value: &str) -> Annotation {
@@ -13,10 +13,12 @@
 //  limitations under the License.
 
 //! Endpoints.
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "detect-ip")]
+use std::net::UdpSocket;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 /// The network context of a node in the service graph.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 pub struct Endpoint {
@@ -42,6 +44,12 @@ impl Endpoint {
         }
     }
 
+    /// Creates an `Endpoint` with the IP address and port of a socket address.
+    #[inline]
+    pub fn from_socket_addr(addr: SocketAddr) -> Endpoint {
+        Endpoint::builder().ip(addr.ip()).port(addr.port()).build()
+    }
+
     /// Returns the name of the service at this endpoint.
     #[inline]
     pub fn service_name(&self) -> Option<&str> {
@@ -65,6 +73,30 @@ impl Endpoint {
     pub fn port(&self) -> Option<u16> {
         self.port
     }
+
+    /// Determines if this endpoint represents the same service as another, ignoring their ports.
+    ///
+    /// The service names, IPv4 addresses, and IPv6 addresses of the endpoints are compared.
+    #[inline]
+    pub fn same_service(&self, other: &Endpoint) -> bool {
+        self.service_name == other.service_name
+            && self.ipv4 == other.ipv4
+            && self.ipv6 == other.ipv6
+    }
+
+    /// Returns the socket address of the service at this endpoint.
+    ///
+    /// The IPv4 address is used if both an IPv4 and IPv6 address are present. Returns `None` if the endpoint has no
+    /// IP address or no port.
+    #[inline]
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        let ip = match (self.ipv4, self.ipv6) {
+            (Some(ipv4), _) => IpAddr::V4(ipv4),
+            (None, Some(ipv6)) => IpAddr::V6(ipv6),
+            (None, None) => return None,
+        };
+        Some(SocketAddr::new(ip, self.port?))
+    }
 }
 
 /// A builder type for `Endpoint`s.
@@ -92,8 +124,11 @@ impl Builder {
     ///
     /// Defaults to `None`.
     #[inline]
-    pub fn service_name(&mut self, service_name: &str) -> &mut Builder {
-        self.service_name = Some(service_name.to_string());
+    pub fn service_name<T>(&mut self, service_name: T) -> &mut Builder
+    where
+        T: Into<String>,
+    {
+        self.service_name = Some(service_name.into());
         self
     }
 
@@ -127,6 +162,35 @@ impl Builder {
         }
     }
 
+    /// Sets the IP addresses associated with the endpoint to those of the local host.
+    ///
+    /// The addresses are those the OS would use to route packets to public IPv4 and IPv6 addresses. No packets are
+    /// actually sent. Addresses which cannot be determined (for example, because the host has no IPv6 connectivity)
+    /// or which are loopback addresses are left unset.
+    ///
+    /// Requires the `detect-ip` Cargo feature.
+    #[cfg(feature = "detect-ip")]
+    pub fn detect_ip(&mut self) -> &mut Builder {
+        // these addresses are arbitrary public IPs - they're only used for route lookups
+        let ipv4 = local_ip(
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::from((Ipv4Addr::new(192, 0, 2, 1), 80)),
+        );
+        if let Some(IpAddr::V4(ipv4)) = ipv4 {
+            self.ipv4(ipv4);
+        }
+
+        let ipv6 = local_ip(
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 80)),
+        );
+        if let Some(IpAddr::V6(ipv6)) = ipv6 {
+            self.ipv6(ipv6);
+        }
+
+        self
+    }
+
     /// Sets the port associated with the endpoint.
     ///
     /// Defaults to `None`.
@@ -136,6 +200,34 @@ impl Builder {
         self
     }
 
+    /// Clears the service name associated with the endpoint.
+    #[inline]
+    pub fn clear_service_name(&mut self) -> &mut Builder {
+        self.service_name = None;
+        self
+    }
+
+    /// Clears the IPv4 address associated with the endpoint.
+    #[inline]
+    pub fn clear_ipv4(&mut self) -> &mut Builder {
+        self.ipv4 = None;
+        self
+    }
+
+    /// Clears the IPv6 address associated with the endpoint.
+    #[inline]
+    pub fn clear_ipv6(&mut self) -> &mut Builder {
+        self.ipv6 = None;
+        self
+    }
+
+    /// Clears the port associated with the endpoint.
+    #[inline]
+    pub fn clear_port(&mut self) -> &mut Builder {
+        self.port = None;
+        self
+    }
+
     /// Constructs the `Endpoint`.
     #[inline]
     pub fn build(&self) -> Endpoint {
@@ -147,3 +239,130 @@ impl Builder {
         }
     }
 }
+
+#[cfg(feature = "detect-ip")]
+fn local_ip(bind: SocketAddr, remote: SocketAddr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect(remote).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    if ip.is_loopback() || ip.is_unspecified() {
+        None
+    } else {
+        Some(ip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn same_service() {
+        let a = Endpoint::builder()
+            .service_name("foo")
+            .ipv4(Ipv4Addr::LOCALHOST)
+            .port(8080)
+            .build();
+
+        let b = Builder::from(a.clone()).port(54321).build();
+        assert!(a.same_service(&b));
+        assert_ne!(a, b);
+
+        let b = Builder::from(a.clone()).clear_port().build();
+        assert!(a.same_service(&b));
+
+        let b = Builder::from(a.clone()).service_name("bar").build();
+        assert!(!a.same_service(&b));
+
+        let b = Builder::from(a.clone()).ipv6(Ipv6Addr::LOCALHOST).build();
+        assert!(!a.same_service(&b));
+    }
+
+    #[test]
+    fn clear_fields() {
+        let endpoint = Endpoint::builder()
+            .service_name("foo")
+            .ipv4(Ipv4Addr::LOCALHOST)
+            .ipv6(Ipv6Addr::LOCALHOST)
+            .port(8080)
+            .build();
+
+        let cleared = Builder::from(endpoint.clone()).clear_service_name().build();
+        assert_eq!(cleared.service_name(), None);
+        assert_eq!(cleared.ipv4(), endpoint.ipv4());
+        assert_eq!(cleared.ipv6(), endpoint.ipv6());
+        assert_eq!(cleared.port(), endpoint.port());
+
+        let cleared = Builder::from(endpoint)
+            .clear_ipv4()
+            .clear_ipv6()
+            .clear_port()
+            .build();
+        assert_eq!(cleared, Endpoint::builder().service_name("foo").build());
+    }
+
+    #[test]
+    fn socket_addr() {
+        let addr = "127.0.0.1:8080".parse::<SocketAddr>().unwrap();
+        let endpoint = Endpoint::from_socket_addr(addr);
+        assert_eq!(endpoint.ipv4(), Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(endpoint.ipv6(), None);
+        assert_eq!(endpoint.port(), Some(8080));
+        assert_eq!(endpoint.socket_addr(), Some(addr));
+
+        let addr = "[::1]:8080".parse::<SocketAddr>().unwrap();
+        let endpoint = Endpoint::from_socket_addr(addr);
+        assert_eq!(endpoint.ipv4(), None);
+        assert_eq!(endpoint.ipv6(), Some(Ipv6Addr::LOCALHOST));
+        assert_eq!(endpoint.socket_addr(), Some(addr));
+
+        let endpoint = Endpoint::builder()
+            .ipv4(Ipv4Addr::LOCALHOST)
+            .ipv6(Ipv6Addr::LOCALHOST)
+            .port(8080)
+            .build();
+        assert_eq!(
+            endpoint.socket_addr(),
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+
+        let endpoint = Endpoint::builder().ipv4(Ipv4Addr::LOCALHOST).build();
+        assert_eq!(endpoint.socket_addr(), None);
+
+        let endpoint = Endpoint::builder().port(8080).build();
+        assert_eq!(endpoint.socket_addr(), None);
+    }
+
+    #[test]
+    fn hash_set() {
+        let a = Endpoint::builder()
+            .service_name("foo")
+            .ipv4(Ipv4Addr::LOCALHOST)
+            .port(80)
+            .build();
+        let b = Endpoint::builder()
+            .service_name("foo")
+            .ipv6(Ipv6Addr::LOCALHOST)
+            .port(80)
+            .build();
+        let c = Endpoint::builder().service_name("foo").port(80).build();
+
+        let mut set = HashSet::new();
+        assert!(set.insert(a.clone()));
+        assert!(set.insert(b.clone()));
+        assert!(set.insert(c.clone()));
+        assert!(!set.insert(a));
+        assert!(!set.insert(b));
+        assert!(!set.insert(c));
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "detect-ip")]
+    #[ignore] // requires a host with a non-loopback network interface
+    fn detect_ip() {
+        let endpoint = Endpoint::builder().detect_ip().build();
+        assert!(endpoint.ipv4().is_some() || endpoint.ipv6().is_some());
+    }
+}
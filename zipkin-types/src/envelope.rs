@@ -0,0 +1,195 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! Versioned span storage.
+//!
+//! Spans are wrapped in an envelope of the form `{"version": 1, "spans": [...]}`. The version is checked when the
+//! envelope is deserialized, and envelopes written with an incompatible schema are rejected rather than silently
+//! misinterpreted.
+//!
+//! Requires the `serde` Cargo feature.
+use crate::Span;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
+use std::fmt;
+
+/// The schema version written by `serialize_spans` and accepted by `deserialize_spans`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const FIELDS: &[&str] = &["version", "spans"];
+
+/// Serializes a list of spans in a versioned envelope.
+pub fn serialize_spans<S>(spans: &[Span], s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut envelope = s.serialize_struct("SpanEnvelope", 2)?;
+    envelope.serialize_field("version", &SCHEMA_VERSION)?;
+    envelope.serialize_field("spans", spans)?;
+    envelope.end()
+}
+
+/// Deserializes a list of spans from a versioned envelope.
+///
+/// Returns an error if the envelope's version is not `SCHEMA_VERSION`.
+pub fn deserialize_spans<'de, D>(d: D) -> Result<Vec<Span>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    d.deserialize_struct("SpanEnvelope", FIELDS, EnvelopeVisitor)
+}
+
+fn check_version<E>(version: u32) -> Result<(), E>
+where
+    E: de::Error,
+{
+    if version == SCHEMA_VERSION {
+        Ok(())
+    } else {
+        Err(E::custom(format_args!(
+            "unsupported span schema version {} (expected {})",
+            version, SCHEMA_VERSION
+        )))
+    }
+}
+
+struct EnvelopeVisitor;
+
+impl<'de> Visitor<'de> for EnvelopeVisitor {
+    type Value = Vec<Span>;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("a versioned span envelope")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Span>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let version = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        check_version(version)?;
+        seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Vec<Span>, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut version = None;
+        let mut spans = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match &*key {
+                "version" => {
+                    if version.is_some() {
+                        return Err(de::Error::duplicate_field("version"));
+                    }
+                    // check the version before the spans are parsed, since an incompatible schema could fail there
+                    // with a less helpful error
+                    let value = map.next_value()?;
+                    check_version(value)?;
+                    version = Some(value);
+                }
+                "spans" => {
+                    if spans.is_some() {
+                        return Err(de::Error::duplicate_field("spans"));
+                    }
+                    spans = Some(map.next_value()?);
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        version.ok_or_else(|| de::Error::missing_field("version"))?;
+        spans.ok_or_else(|| de::Error::missing_field("spans"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Annotation, Endpoint, Kind, SpanId, TraceId};
+    use std::time::{Duration, SystemTime};
+
+    fn to_json(spans: &[Span]) -> String {
+        let mut buf = vec![];
+        serialize_spans(spans, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn from_json(json: &str) -> Result<Vec<Span>, serde_json::Error> {
+        deserialize_spans(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    #[test]
+    fn round_trip() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let spans = vec![
+            Span::builder()
+                .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+                .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+                .name("get")
+                .kind(Kind::Server)
+                .timestamp(start)
+                .duration(Duration::from_millis(10))
+                .local_endpoint(Endpoint::builder().service_name("server").build())
+                .annotation(Annotation::new(start, "wr"))
+                .tag("http.path", "/foo")
+                .build(),
+            Span::builder()
+                .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+                .id(SpanId::from([2, 3, 4, 5, 6, 7, 8, 9]))
+                .parent_id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+                .build(),
+        ];
+
+        let json = to_json(&spans);
+        assert!(json.starts_with(r#"{"version":1,"spans":["#));
+
+        let decoded = from_json(&json).unwrap();
+        assert_eq!(to_json(&decoded), json);
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].name(), Some("get"));
+        assert_eq!(decoded[0].timestamp(), Some(start));
+        assert_eq!(decoded[1].timestamp(), None);
+        assert_eq!(decoded[1].parent_id(), spans[1].parent_id());
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let json = to_json(&[]);
+        assert_eq!(json, r#"{"version":1,"spans":[]}"#);
+        assert!(from_json(&json).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reject_version() {
+        let err = from_json(r#"{"version":2,"spans":[{"unknown":true}]}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported span schema version 2"));
+
+        let err = from_json(r#"{"spans":[],"version":2}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported span schema version 2"));
+
+        assert!(from_json(r#"{"spans":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! A lightweight JSON encoder for spans.
+//!
+//! By default, the encoder produces the same output as serializing spans with `serde_json`, without depending on
+//! `serde`. With `TimestampEncoding::String`, it produces the same output as serializing spans with
+//! `span::serialize_string_timestamps`.
+//!
+//! Requires the `json` Cargo feature.
+use crate::{Annotation, Endpoint, Kind, Span};
+use std::fmt;
+use std::io::{self, Write};
+
+/// The JSON representation of timestamps.
+///
+/// Zipkin timestamps are microseconds since the Unix epoch. Some JSON parsers, notably JavaScript's, read every number
+/// as a 64 bit float, so collectors built on them may require timestamps to be encoded as strings to be sure they are
+/// not rounded.
+///
+/// When serializing spans with `serde`, string timestamps are available through `span::serialize_string_timestamps`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampEncoding {
+    /// Timestamps are encoded as JSON numbers, like `1500000000123`.
+    Number,
+
+    /// Timestamps are encoded as JSON strings, like `"1500000000123"`.
+    String,
+}
+
+impl Default for TimestampEncoding {
+    #[inline]
+    fn default() -> TimestampEncoding {
+        TimestampEncoding::Number
+    }
+}
+
+/// Encodes a span in the Zipkin v2 JSON format, appending it to a buffer.
+pub fn encode_span(span: &Span, buf: &mut Vec<u8>) {
+    encode_span_inner(span, TimestampEncoding::Number, buf);
+}
+
+/// Returns the length in bytes of a span encoded in the Zipkin v2 JSON format.
+///
+/// The span is not actually written to a buffer, so no memory is allocated.
+pub fn encoded_len(span: &Span) -> usize {
+    let mut counter = Counter(0);
+    encode_span_inner(span, TimestampEncoding::Number, &mut counter);
+    counter.0
+}
+
+fn encode_span_inner<B>(span: &Span, timestamps: TimestampEncoding, buf: &mut B)
+where
+    B: Buf,
+{
+    buf.push(b'{');
+    let mut first = true;
+
+    field(buf, &mut first, "traceId");
+    write_display(buf, span.trace_id());
+    if let Some(name) = span.name() {
+        field(buf, &mut first, "name");
+        write_str(buf, name);
+    }
+    if let Some(parent_id) = span.parent_id() {
+        field(buf, &mut first, "parentId");
+        write_display(buf, parent_id);
+    }
+    field(buf, &mut first, "id");
+    write_display(buf, span.id());
+    if let Some(kind) = span.kind() {
+        field(buf, &mut first, "kind");
+        write_str(buf, kind_name(kind));
+    }
+    if let Some(timestamp) = span.timestamp() {
+        field(buf, &mut first, "timestamp");
+        write_timestamp(buf, timestamps, crate::time_micros::to_wire(&timestamp));
+    }
+    if let Some(duration) = span.duration() {
+        field(buf, &mut first, "duration");
+        write!(buf, "{}", crate::duration_micros::to_wire(&duration)).unwrap();
+    }
+    if span.debug() {
+        field(buf, &mut first, "debug");
+        buf.extend_from_slice(b"true");
+    }
+    if span.shared() {
+        field(buf, &mut first, "shared");
+        buf.extend_from_slice(b"true");
+    }
+    if let Some(local_endpoint) = span.local_endpoint() {
+        field(buf, &mut first, "localEndpoint");
+        encode_endpoint(local_endpoint, buf);
+    }
+    if let Some(remote_endpoint) = span.remote_endpoint() {
+        field(buf, &mut first, "remoteEndpoint");
+        encode_endpoint(remote_endpoint, buf);
+    }
+    if !span.annotations().is_empty() {
+        field(buf, &mut first, "annotations");
+        buf.push(b'[');
+        for (i, annotation) in span.annotations().iter().enumerate() {
+            if i != 0 {
+                buf.push(b',');
+            }
+            encode_annotation(annotation, timestamps, buf);
+        }
+        buf.push(b']');
+    }
+    if !span.tags().is_empty() {
+        field(buf, &mut first, "tags");
+        buf.push(b'{');
+        let mut first_tag = true;
+        for (key, value) in span.tags_sorted() {
+            field(buf, &mut first_tag, key);
+            write_str(buf, value);
+        }
+        buf.push(b'}');
+    }
+
+    buf.push(b'}');
+}
+
+/// Encodes a list of spans in the Zipkin v2 JSON format, appending it to a buffer.
+pub fn encode_spans(spans: &[Span], buf: &mut Vec<u8>) {
+    encode_spans_with(spans, TimestampEncoding::Number, buf);
+}
+
+/// Like `encode_spans`, but with a specific encoding of the spans' timestamps.
+///
+/// The encoding applies to the timestamps of both spans and their annotations.
+pub fn encode_spans_with(spans: &[Span], timestamps: TimestampEncoding, buf: &mut Vec<u8>) {
+    buf.push(b'[');
+    for (i, span) in spans.iter().enumerate() {
+        if i != 0 {
+            buf.push(b',');
+        }
+        encode_span_inner(span, timestamps, buf);
+    }
+    buf.push(b']');
+}
+
+fn encode_endpoint<B>(endpoint: &Endpoint, buf: &mut B)
+where
+    B: Buf,
+{
+    buf.push(b'{');
+    let mut first = true;
+
+    if let Some(service_name) = endpoint.service_name() {
+        field(buf, &mut first, "serviceName");
+        write_str(buf, service_name);
+    }
+    if let Some(ipv4) = endpoint.ipv4() {
+        field(buf, &mut first, "ipv4");
+        write_display(buf, ipv4);
+    }
+    if let Some(ipv6) = endpoint.ipv6() {
+        field(buf, &mut first, "ipv6");
+        write_display(buf, ipv6);
+    }
+    if let Some(port) = endpoint.port() {
+        field(buf, &mut first, "port");
+        write!(buf, "{}", port).unwrap();
+    }
+
+    buf.push(b'}');
+}
+
+fn encode_annotation<B>(annotation: &Annotation, timestamps: TimestampEncoding, buf: &mut B)
+where
+    B: Buf,
+{
+    buf.push(b'{');
+    let mut first = true;
+
+    field(buf, &mut first, "timestamp");
+    write_timestamp(
+        buf,
+        timestamps,
+        crate::time_micros::to_wire(&annotation.timestamp()),
+    );
+    field(buf, &mut first, "value");
+    write_str(buf, annotation.value());
+
+    buf.push(b'}');
+}
+
+fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Client => "CLIENT",
+        Kind::Server => "SERVER",
+        Kind::Producer => "PRODUCER",
+        Kind::Consumer => "CONSUMER",
+    }
+}
+
+fn field<B>(buf: &mut B, first: &mut bool, name: &str)
+where
+    B: Buf,
+{
+    if !*first {
+        buf.push(b',');
+    }
+    *first = false;
+    write_str(buf, name);
+    buf.push(b':');
+}
+
+fn write_timestamp<B>(buf: &mut B, timestamps: TimestampEncoding, micros: u64)
+where
+    B: Buf,
+{
+    match timestamps {
+        TimestampEncoding::Number => write!(buf, "{}", micros).unwrap(),
+        TimestampEncoding::String => write_display(buf, micros),
+    }
+}
+
+// only for values whose representations never need to be escaped
+fn write_display<B, T>(buf: &mut B, value: T)
+where
+    B: Buf,
+    T: fmt::Display,
+{
+    write!(buf, "\"{}\"", value).unwrap();
+}
+
+fn write_str<B>(buf: &mut B, s: &str)
+where
+    B: Buf,
+{
+    buf.push(b'"');
+
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape = match b {
+            b'"' => b'"',
+            b'\\' => b'\\',
+            b'\x08' => b'b',
+            b'\x0c' => b'f',
+            b'\n' => b'n',
+            b'\r' => b'r',
+            b'\t' => b't',
+            0x00..=0x1f => b'u',
+            _ => continue,
+        };
+
+        buf.extend_from_slice(&s.as_bytes()[start..i]);
+        buf.push(b'\\');
+        buf.push(escape);
+        if escape == b'u' {
+            write!(buf, "{:04x}", b).unwrap();
+        }
+        start = i + 1;
+    }
+    buf.extend_from_slice(&s.as_bytes()[start..]);
+
+    buf.push(b'"');
+}
+
+// a minimal abstraction over the output of the encoder, so it can either write to a buffer or count bytes
+trait Buf: Write {
+    fn push(&mut self, b: u8);
+
+    fn extend_from_slice(&mut self, s: &[u8]);
+}
+
+impl Buf for Vec<u8> {
+    #[inline]
+    fn push(&mut self, b: u8) {
+        Vec::push(self, b)
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, s: &[u8]) {
+        Vec::extend_from_slice(self, s)
+    }
+}
+
+struct Counter(usize);
+
+impl Write for Counter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buf for Counter {
+    #[inline]
+    fn push(&mut self, _: u8) {
+        self.0 += 1;
+    }
+
+    #[inline]
+    fn extend_from_slice(&mut self, s: &[u8]) {
+        self.0 += s.len();
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+    use crate::{SpanId, TraceId};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn check(span: &Span) {
+        let mut buf = vec![];
+        encode_span(span, &mut buf);
+        let expected = serde_json::to_string(span).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+        assert_eq!(encoded_len(span), expected.len());
+        assert_eq!(span.serialized_len(), expected.len());
+    }
+
+    #[test]
+    fn minimal() {
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .build();
+        check(&span);
+    }
+
+    #[test]
+    fn full() {
+        let span = Span::builder()
+            .trace_id(TraceId::from([
+                0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+            ]))
+            .name("get /foo")
+            .parent_id(SpanId::from([2, 3, 4, 5, 6, 7, 8, 9]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .kind(Kind::Server)
+            .timestamp(UNIX_EPOCH + Duration::from_micros(1_500_000_123))
+            .duration(Duration::from_nanos(1_500))
+            .debug(true)
+            .shared(true)
+            .local_endpoint(
+                Endpoint::builder()
+                    .service_name("foo")
+                    .ipv4(Ipv4Addr::new(10, 0, 0, 1))
+                    .port(8080)
+                    .build(),
+            )
+            .remote_endpoint(Endpoint::builder().ipv6(Ipv6Addr::LOCALHOST).build())
+            .annotation(Annotation::new(
+                UNIX_EPOCH + Duration::from_micros(1_500_000_500),
+                "ws",
+            ))
+            .annotation(Annotation::new(
+                UNIX_EPOCH + Duration::from_micros(1_500_000_600),
+                "wr",
+            ))
+            .tag("http.method", "GET")
+            .tag(
+                "error",
+                "quote \" backslash \\ newline \n tab \t nul \0 bell \x07 del \x7f",
+            )
+            .tag("unicode", "\u{e9}\u{1f600}")
+            .build();
+        check(&span);
+    }
+
+    #[test]
+    fn spans() {
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .kind(Kind::Client)
+            .build();
+        let spans = vec![span.clone(), span];
+
+        let mut buf = vec![];
+        encode_spans(&spans, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            serde_json::to_string(&spans).unwrap()
+        );
+
+        let mut buf = vec![];
+        encode_spans(&[], &mut buf);
+        assert_eq!(buf, b"[]");
+    }
+
+    #[test]
+    fn timestamp_encoding() {
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .timestamp(UNIX_EPOCH + Duration::from_micros(1_500_000_000_123))
+            .duration(Duration::from_micros(10))
+            .annotation(Annotation::new(
+                UNIX_EPOCH + Duration::from_micros(1_500_000_000_456),
+                "ws",
+            ))
+            .build();
+
+        let spans = vec![span];
+
+        let mut buf = vec![];
+        encode_spans_with(&spans, TimestampEncoding::Number, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            serde_json::to_string(&spans).unwrap()
+        );
+
+        #[derive(serde::Serialize)]
+        struct StringTimestamps<'a>(
+            #[serde(serialize_with = "crate::span::serialize_string_timestamps")] &'a Span,
+        );
+
+        let mut buf = vec![];
+        encode_spans_with(&spans, TimestampEncoding::String, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            serde_json::to_string(&[StringTimestamps(&spans[0])]).unwrap()
+        );
+        let value = serde_json::from_slice::<serde_json::Value>(&buf).unwrap();
+        assert_eq!(value[0]["timestamp"], "1500000000123");
+        assert_eq!(value[0]["duration"], 10);
+        assert_eq!(value[0]["annotations"][0]["timestamp"], "1500000000456");
+    }
+}
@@ -21,29 +21,55 @@
 //! If the `serde` Cargo feature is enabled, `Annotation`, `Endpoint`, `Kind`, `Span`, `SpanId`, and
 //! `TraceId` implement `Serialize` and `Deserialize` in the standard Zipkin format.
 //!
+//! The feature also enables the `serialize_spans` and `deserialize_spans` functions, which store a list of spans in an
+//! envelope carrying a schema version so that spans can be persisted and safely reloaded later. For collectors which
+//! require timestamps to be encoded as strings, the `time_micros_string` module and the
+//! `span::serialize_string_timestamps` function provide `serialize_with` variants.
+//!
+//! If the `json` Cargo feature is enabled, the `json` module provides a lightweight encoder for spans in the standard
+//! Zipkin JSON format which does not depend on `serde`.
+//!
 //! [specification]: https://github.com/openzipkin/zipkin-api/blob/master/zipkin2-api.yaml
 #![doc(html_root_url = "https://docs.rs/zipkin-types/0.1")]
 #![warn(missing_docs)]
 
+use std::convert::TryFrom;
+use std::time::Duration;
+
 #[doc(inline)]
 pub use crate::annotation::Annotation;
 #[doc(inline)]
 pub use crate::endpoint::Endpoint;
+#[cfg(feature = "serde")]
 #[doc(inline)]
-pub use crate::span::{Kind, Span};
+pub use crate::envelope::{deserialize_spans, serialize_spans};
+#[doc(inline)]
+pub use crate::span::{Kind, Span, TagKeyPolicy};
 #[doc(inline)]
 pub use crate::span_id::SpanId;
 #[doc(inline)]
+pub use crate::tag_key::TagKey;
+#[doc(inline)]
 pub use crate::trace_id::TraceId;
 
 pub mod annotation;
 pub mod endpoint;
+#[cfg(feature = "serde")]
+pub mod envelope;
+#[cfg(feature = "json")]
+pub mod json;
+// the encoder also backs Span::serialized_len
+#[cfg(all(feature = "serde", not(feature = "json")))]
+#[allow(dead_code)]
+mod json;
 pub mod span;
 pub mod span_id;
+pub mod tag_key;
 pub mod trace_id;
 
-#[cfg(feature = "serde")]
+#[cfg(any(feature = "serde", feature = "json"))]
 mod time_micros {
+    #[cfg(feature = "serde")]
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -55,11 +81,13 @@ mod time_micros {
         )
     }
 
+    #[cfg(feature = "serde")]
     pub fn from_wire(time: u64) -> SystemTime {
         let duration = super::duration_micros::from_wire(time);
         UNIX_EPOCH + duration
     }
 
+    #[cfg(feature = "serde")]
     pub fn serialize<S>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -67,6 +95,7 @@ mod time_micros {
         to_wire(time).serialize(s)
     }
 
+    #[cfg(feature = "serde")]
     pub fn deserialize<'de, D>(d: D) -> Result<SystemTime, D::Error>
     where
         D: Deserializer<'de>,
@@ -75,19 +104,68 @@ mod time_micros {
     }
 }
 
-#[cfg(feature = "serde")]
+#[cfg(any(feature = "serde", feature = "json"))]
 mod duration_micros {
     use std::time::Duration;
 
     pub fn to_wire(duration: &Duration) -> u64 {
-        let micros = duration.as_secs() * 1_000_000 + duration.subsec_nanos() as u64 / 1_000;
-        micros.max(1)
+        super::duration_to_micros(*duration)
     }
 
+    #[cfg(feature = "serde")]
     pub fn from_wire(duration: u64) -> Duration {
-        let seconds = duration / 1_000_000;
-        let subsec_nanos = (duration % 1_000_000) * 1_000;
-        Duration::new(seconds, subsec_nanos as u32)
+        super::micros_to_duration(duration)
+    }
+}
+
+/// Converts a duration to the number of microseconds used to represent it in the Zipkin wire format.
+///
+/// Sub-microsecond precision is truncated, but the result is never less than 1 since Zipkin does not accept a
+/// duration of 0. Durations too large to represent saturate to `u64::MAX`.
+pub fn duration_to_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros())
+        .unwrap_or(u64::MAX)
+        .max(1)
+}
+
+/// Converts a number of microseconds in the Zipkin wire format to a duration.
+pub fn micros_to_duration(micros: u64) -> Duration {
+    Duration::from_micros(micros)
+}
+
+/// Serde serialization of timestamps as strings.
+///
+/// Zipkin timestamps are microseconds since the Unix epoch, and are normally serialized as numbers. Some JSON parsers,
+/// notably JavaScript's, read every number as a 64 bit float, so collectors built on them may require timestamps to be
+/// encoded as strings to be sure they are not rounded. This module can be used with `#[serde(with = "...")]` on a
+/// `SystemTime` field to encode it as a string like `"1500000000123"`. `span::serialize_string_timestamps` does the
+/// same for all of a span's timestamps.
+///
+/// Requires the `serde` Cargo feature.
+#[cfg(feature = "serde")]
+pub mod time_micros_string {
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::Serializer;
+    use std::borrow::Cow;
+    use std::time::SystemTime;
+
+    /// Serializes a timestamp as a string of microseconds since the Unix epoch.
+    pub fn serialize<S>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.collect_str(&super::time_micros::to_wire(time))
+    }
+
+    /// Deserializes a timestamp from a string of microseconds since the Unix epoch.
+    pub fn deserialize<'de, D>(d: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = Cow::<str>::deserialize(d)?;
+        s.parse()
+            .map(super::time_micros::from_wire)
+            .map_err(de::Error::custom)
     }
 }
 
@@ -136,3 +214,31 @@ mod opt_duration_micros {
         Option::<u64>::deserialize(d).map(|o| o.map(super::duration_micros::from_wire))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn duration_micros() {
+        assert_eq!(duration_to_micros(Duration::from_secs(0)), 1);
+        assert_eq!(duration_to_micros(Duration::from_nanos(999)), 1);
+        assert_eq!(duration_to_micros(Duration::from_nanos(1_999)), 1);
+        assert_eq!(duration_to_micros(Duration::from_nanos(2_000)), 2);
+        assert_eq!(
+            duration_to_micros(Duration::from_secs(90_000)),
+            90_000_000_000
+        );
+        assert_eq!(duration_to_micros(Duration::from_secs(u64::MAX)), u64::MAX);
+
+        assert_eq!(micros_to_duration(1), Duration::from_micros(1));
+        assert_eq!(
+            micros_to_duration(90_000_000_001),
+            Duration::new(90_000, 1_000)
+        );
+        assert_eq!(
+            micros_to_duration(duration_to_micros(Duration::from_millis(1500))),
+            Duration::from_millis(1500)
+        );
+    }
+}
@@ -14,7 +14,13 @@
 
 //! Spans.
 use crate::{Annotation, Endpoint, SpanId, TraceId};
+use data_encoding::BASE64;
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::mem;
 use std::time::{Duration, SystemTime};
 
 /// The "kind" of a span.
@@ -51,11 +57,34 @@ pub enum Kind {
     ///
     /// * Timestamp - The moment a message was received from an origin (formerly "mr")
     /// * Duration - When present, represents the delay consuming the message, such as from a
-    ///     backlog.
+    ///   backlog.
     /// * Remote Endpoint - Represents the broker.
     Consumer,
 }
 
+/// The handling of tag keys containing control characters like newlines.
+///
+/// Some Zipkin storage backends do not support keys containing control characters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TagKeyPolicy {
+    /// Each control character in the key is replaced with `_`.
+    Sanitize,
+
+    /// The tag is discarded.
+    Reject,
+
+    /// The key is used as-is.
+    Allow,
+}
+
+impl Default for TagKeyPolicy {
+    #[inline]
+    fn default() -> TagKeyPolicy {
+        TagKeyPolicy::Sanitize
+    }
+}
+
 /// A `Span` represents a single operation over some range of time.
 ///
 /// Multiple spans make up a single "trace" of a distributed computation, and
@@ -68,6 +97,9 @@ pub enum Kind {
 /// span, and the server span should omit that information. The client and
 /// server may both add their own annotations and binary annotations the span -
 /// they will be merged.
+///
+/// When serialized, a span's tags are always sorted by key so that the serialized representation of a span is
+/// deterministic.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
@@ -84,7 +116,8 @@ pub struct Span {
         feature = "serde",
         serde(
             skip_serializing_if = "Option::is_none",
-            with = "crate::opt_time_micros"
+            with = "crate::opt_time_micros",
+            default
         )
     )]
     timestamp: Option<SystemTime>,
@@ -92,7 +125,8 @@ pub struct Span {
         feature = "serde",
         serde(
             skip_serializing_if = "Option::is_none",
-            with = "crate::opt_duration_micros"
+            with = "crate::opt_duration_micros",
+            default
         )
     )]
     duration: Option<Duration>,
@@ -117,7 +151,11 @@ pub struct Span {
     annotations: Vec<Annotation>,
     #[cfg_attr(
         feature = "serde",
-        serde(skip_serializing_if = "HashMap::is_empty", default)
+        serde(
+            skip_serializing_if = "HashMap::is_empty",
+            serialize_with = "serialize_tags",
+            default
+        )
     )]
     tags: HashMap<String, String>,
 }
@@ -134,6 +172,109 @@ fn value_false() -> bool {
     false
 }
 
+/// Serializes a span with the timestamps of the span and its annotations encoded as strings.
+///
+/// The output is otherwise the same as that of the span's `Serialize` implementation. This can be used with
+/// `#[serde(serialize_with = "...")]` on a `Span` field for collectors which require string timestamps. See
+/// `time_micros_string` for details.
+///
+/// Requires the `serde` Cargo feature.
+#[cfg(feature = "serde")]
+pub fn serialize_string_timestamps<S>(span: &Span, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serde::Serialize::serialize(
+        &StringTimestampSpan {
+            trace_id: span.trace_id,
+            name: span.name.as_deref(),
+            parent_id: span.parent_id,
+            id: span.id,
+            kind: span.kind,
+            timestamp: span.timestamp,
+            duration: span.duration,
+            debug: span.debug,
+            shared: span.shared,
+            local_endpoint: span.local_endpoint.as_ref(),
+            remote_endpoint: span.remote_endpoint.as_ref(),
+            annotations: span
+                .annotations
+                .iter()
+                .map(|a| StringTimestampAnnotation {
+                    timestamp: a.timestamp(),
+                    value: a.value(),
+                })
+                .collect(),
+            tags: span.tags.iter().collect(),
+        },
+        s,
+    )
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StringTimestampSpan<'a> {
+    trace_id: TraceId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<SpanId>,
+    id: SpanId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<Kind>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_opt_string_timestamp"
+    )]
+    timestamp: Option<SystemTime>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::opt_duration_micros::serialize"
+    )]
+    duration: Option<Duration>,
+    #[serde(skip_serializing_if = "is_false")]
+    debug: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    shared: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_endpoint: Option<&'a Endpoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_endpoint: Option<&'a Endpoint>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<StringTimestampAnnotation<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    tags: BTreeMap<&'a String, &'a String>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct StringTimestampAnnotation<'a> {
+    #[serde(with = "crate::time_micros_string")]
+    timestamp: SystemTime,
+    value: &'a str,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_opt_string_timestamp<S>(time: &Option<SystemTime>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match *time {
+        Some(ref time) => crate::time_micros_string::serialize(time, s),
+        None => s.serialize_none(),
+    }
+}
+
+// tags are serialized sorted by key so that the output is deterministic
+#[cfg(feature = "serde")]
+fn serialize_tags<S>(tags: &HashMap<String, String>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    s.collect_map(tags.iter().collect::<BTreeMap<_, _>>())
+}
+
 impl Span {
     /// Returns a builder used to construct a `Span`.
     #[inline]
@@ -152,6 +293,7 @@ impl Span {
             remote_endpoint: None,
             annotations: vec![],
             tags: HashMap::new(),
+            tag_key_policy: TagKeyPolicy::default(),
         }
     }
 
@@ -200,8 +342,10 @@ impl Span {
 
     /// The duration of the critical path, if known.
     ///
-    /// Durations are recorded in microseconds, and rounded up to a minimum of 1. Durations of
-    /// children can be longer than their parents due to asynchronous operations.
+    /// This is the duration the span was built with, at full precision. The Zipkin wire format records durations in
+    /// microseconds, so the serialized duration is converted with `duration_to_micros`, truncating it to a whole
+    /// number of microseconds with a minimum of 1. Durations of children can be longer than their parents due to
+    /// asynchronous operations.
     #[inline]
     pub fn duration(&self) -> Option<Duration> {
         self.duration
@@ -247,8 +391,176 @@ impl Span {
     pub fn tags(&self) -> &HashMap<String, String> {
         &self.tags
     }
+
+    /// Returns the value of a tag set by `Builder::tag_bytes`, decoded from base64.
+    ///
+    /// Returns `None` if the tag is not present or its value is not valid base64.
+    #[inline]
+    pub fn tag_bytes(&self, key: &str) -> Option<Vec<u8>> {
+        BASE64.decode(self.tags.get(key)?.as_bytes()).ok()
+    }
+
+    /// Merges another span with the same ID into this one.
+    ///
+    /// This is typically used to combine the client and server halves of a span recorded by different tracers, or
+    /// partial copies of a span reported more than once.
+    ///
+    /// Optional fields set in only one of the spans are taken from that span. If a field is set in both spans, the
+    /// value from `self` takes precedence. The merged span is flagged as debug if either span is. The annotations of
+    /// `other` are appended to those of `self`, and the tags of `other` are added to those of `self` unless a tag with
+    /// the same key is already present.
+    ///
+    /// If one span is a `Client` span and the other a `Server` span, the result keeps the kind and local endpoint of
+    /// `self`, and its remote endpoint is the local endpoint of `other`. A merged `Client` span is never shared, and
+    /// a merged `Server` span is shared if `self` is. Otherwise, the merged span is flagged as shared if either span
+    /// is.
+    ///
+    /// Returns an error if the trace IDs or span IDs of the two spans differ, or if both spans have a kind and they
+    /// are neither the same nor a `Client` and `Server` pair.
+    pub fn merge(mut self, other: Span) -> Result<Span, MergeError> {
+        if self.trace_id != other.trace_id {
+            return Err(MergeError(MergeErrorKind::TraceId));
+        }
+        if self.id != other.id {
+            return Err(MergeError(MergeErrorKind::SpanId));
+        }
+
+        let halves = match (self.kind, other.kind) {
+            (Some(Kind::Client), Some(Kind::Server)) | (Some(Kind::Server), Some(Kind::Client)) => {
+                true
+            }
+            (Some(a), Some(b)) if mem::discriminant(&a) != mem::discriminant(&b) => {
+                return Err(MergeError(MergeErrorKind::Kind));
+            }
+            _ => false,
+        };
+
+        self.name = self.name.or(other.name);
+        self.parent_id = self.parent_id.or(other.parent_id);
+        self.kind = self.kind.or(other.kind);
+        self.timestamp = self.timestamp.or(other.timestamp);
+        self.duration = self.duration.or(other.duration);
+        self.debug |= other.debug;
+        if halves {
+            self.shared &= matches!(self.kind, Some(Kind::Server));
+            self.remote_endpoint = other.local_endpoint.or(self.remote_endpoint);
+        } else {
+            self.shared |= other.shared;
+            self.local_endpoint = self.local_endpoint.or(other.local_endpoint);
+            self.remote_endpoint = self.remote_endpoint.or(other.remote_endpoint);
+        }
+        self.annotations.extend(other.annotations);
+        for (key, value) in other.tags {
+            self.tags.entry(key).or_insert(value);
+        }
+
+        Ok(self)
+    }
+
+    /// Sorts the span's annotations by timestamp.
+    ///
+    /// Annotations are stored in the order they were added, which may not be chronological if some were backdated.
+    #[inline]
+    pub fn sort_annotations(&mut self) {
+        self.annotations.sort();
+    }
+
+    /// Checks the span for combinations of fields which are not meaningful to Zipkin.
+    ///
+    /// Zipkin accepts spans which violate these rules, so they are not enforced when a span is built, but they
+    /// typically indicate a bug in instrumentation. The rules are:
+    ///
+    /// * A span with a duration must have a timestamp, since the duration is measured from it.
+    /// * A span with a remote endpoint must have a kind, since spans without a kind represent local operations and
+    ///   the kind determines what the remote endpoint represents.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.duration.is_some() && self.timestamp.is_none() {
+            return Err(ValidationError(
+                ValidationErrorKind::DurationWithoutTimestamp,
+            ));
+        }
+
+        if self.remote_endpoint.is_some() && self.kind.is_none() {
+            return Err(ValidationError(
+                ValidationErrorKind::RemoteEndpointWithoutKind,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the length in bytes of the span encoded in the Zipkin v2 JSON format.
+    ///
+    /// This matches the length of the span serialized with `serde_json`, but does not allocate.
+    ///
+    /// Requires the `serde` or `json` Cargo feature.
+    #[cfg(any(feature = "serde", feature = "json"))]
+    #[inline]
+    pub fn serialized_len(&self) -> usize {
+        crate::json::encoded_len(self)
+    }
+
+    /// Returns an iterator over the span's tags, sorted by key.
+    #[inline]
+    pub fn tags_sorted(&self) -> impl Iterator<Item = (&str, &str)> {
+        let mut tags = self
+            .tags
+            .iter()
+            .map(|(k, v)| (&**k, &**v))
+            .collect::<Vec<_>>();
+        tags.sort_unstable_by_key(|&(k, _)| k);
+        tags.into_iter()
+    }
 }
 
+/// The error returned by `Span::validate`.
+#[derive(Debug)]
+pub struct ValidationError(ValidationErrorKind);
+
+#[derive(Debug)]
+enum ValidationErrorKind {
+    DurationWithoutTimestamp,
+    RemoteEndpointWithoutKind,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.0 {
+            ValidationErrorKind::DurationWithoutTimestamp => "span has a duration but no timestamp",
+            ValidationErrorKind::RemoteEndpointWithoutKind => {
+                "span has a remote endpoint but no kind"
+            }
+        };
+        fmt.write_str(message)
+    }
+}
+
+impl Error for ValidationError {}
+
+/// The error returned by `Span::merge`.
+#[derive(Debug)]
+pub struct MergeError(MergeErrorKind);
+
+#[derive(Debug)]
+enum MergeErrorKind {
+    TraceId,
+    SpanId,
+    Kind,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.0 {
+            MergeErrorKind::TraceId => "merged spans have different trace IDs",
+            MergeErrorKind::SpanId => "merged spans have different span IDs",
+            MergeErrorKind::Kind => "merged spans have incompatible kinds",
+        };
+        fmt.write_str(message)
+    }
+}
+
+impl Error for MergeError {}
+
 /// A builder for `Span`s.
 pub struct Builder {
     trace_id: Option<TraceId>,
@@ -264,6 +576,7 @@ pub struct Builder {
     remote_endpoint: Option<Endpoint>,
     annotations: Vec<Annotation>,
     tags: HashMap<String, String>,
+    tag_key_policy: TagKeyPolicy,
 }
 
 impl From<Span> for Builder {
@@ -283,6 +596,7 @@ impl From<Span> for Builder {
             remote_endpoint: s.remote_endpoint,
             annotations: s.annotations,
             tags: s.tags,
+            tag_key_policy: TagKeyPolicy::default(),
         }
     }
 }
@@ -299,8 +613,11 @@ impl Builder {
     ///
     /// Defaults to `None`.
     #[inline]
-    pub fn name(&mut self, name: &str) -> &mut Builder {
-        self.name = Some(name.to_lowercase());
+    pub fn name<T>(&mut self, name: T) -> &mut Builder
+    where
+        T: Into<String>,
+    {
+        self.name = Some(name.into());
         self
     }
 
@@ -340,6 +657,9 @@ impl Builder {
 
     /// Sets the duration of the span.
     ///
+    /// The duration is stored as-is, and is only converted to whole microseconds when the span is serialized. Use
+    /// `duration_micros` to set a duration which matches the serialized value exactly.
+    ///
     /// Defaults to `None`.
     #[inline]
     pub fn duration(&mut self, duration: Duration) -> &mut Builder {
@@ -347,6 +667,17 @@ impl Builder {
         self
     }
 
+    /// Sets the duration of the span in microseconds, the unit of the Zipkin wire format.
+    ///
+    /// Unlike `duration`, the stored duration is identical to the serialized one, except that a duration of 0 is
+    /// serialized as 1 since Zipkin does not accept empty durations.
+    ///
+    /// Defaults to `None`.
+    #[inline]
+    pub fn duration_micros(&mut self, duration: u64) -> &mut Builder {
+        self.duration(crate::micros_to_duration(duration))
+    }
+
     /// Sets the debug state of the span.
     ///
     /// Defaults to `false`.
@@ -390,6 +721,19 @@ impl Builder {
         self
     }
 
+    /// Adds an annotation to the span at an offset from the span's timestamp.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the span's timestamp has not been set.
+    #[inline]
+    pub fn annotation_offset(&mut self, offset: Duration, value: &str) -> &mut Builder {
+        let base = self
+            .timestamp
+            .expect("span timestamp must be set before adding offset annotations");
+        self.annotation(Annotation::at_offset(base, offset, value))
+    }
+
     /// Adds multiple annotations to the span.
     #[inline]
     pub fn annotations<I>(&mut self, annotations: I) -> &mut Builder
@@ -400,23 +744,101 @@ impl Builder {
         self
     }
 
+    /// Returns the annotations currently added to the span.
+    #[inline]
+    pub fn get_annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Removes all annotations from the span.
+    #[inline]
+    pub fn clear_annotations(&mut self) -> &mut Builder {
+        self.annotations.clear();
+        self
+    }
+
+    /// Sets the handling of tag keys containing control characters.
+    ///
+    /// The policy applies to tags added after it is set.
+    ///
+    /// Defaults to `TagKeyPolicy::Sanitize`.
+    #[inline]
+    pub fn tag_key_policy(&mut self, tag_key_policy: TagKeyPolicy) -> &mut Builder {
+        self.tag_key_policy = tag_key_policy;
+        self
+    }
+
     /// Adds a tag to the span.
+    ///
+    /// The key is normalized according to the builder's `TagKeyPolicy`.
     #[inline]
-    pub fn tag(&mut self, key: &str, value: &str) -> &mut Builder {
-        self.tags.insert(key.to_string(), value.to_string());
+    pub fn tag<K, V>(&mut self, key: K, value: V) -> &mut Builder
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.insert_tag(key.into(), value.into());
+        self
+    }
+
+    /// Adds a tag to the span, formatting its value with its `Display` implementation.
+    ///
+    /// The key is normalized according to the builder's `TagKeyPolicy`.
+    #[inline]
+    pub fn tag_value<K, V>(&mut self, key: K, value: V) -> &mut Builder
+    where
+        K: Into<String>,
+        V: Display,
+    {
+        self.insert_tag(key.into(), value.to_string());
+        self
+    }
+
+    /// Adds a tag with a binary value to the span.
+    ///
+    /// Tag values must be strings, so the value is encoded with standard base64, which increases its size by a third.
+    /// `Span::tag_bytes` can be used to decode the value.
+    ///
+    /// The key is normalized according to the builder's `TagKeyPolicy`.
+    #[inline]
+    pub fn tag_bytes<K>(&mut self, key: K, value: &[u8]) -> &mut Builder
+    where
+        K: Into<String>,
+    {
+        self.insert_tag(key.into(), BASE64.encode(value));
         self
     }
 
     /// As multiple tags to the span.
+    ///
+    /// The keys are normalized according to the builder's `TagKeyPolicy`.
     #[inline]
     pub fn tags<I>(&mut self, tags: I) -> &mut Builder
     where
         I: IntoIterator<Item = (String, String)>,
     {
-        self.tags.extend(tags);
+        for (key, value) in tags {
+            self.insert_tag(key, value);
+        }
         self
     }
 
+    fn insert_tag(&mut self, key: String, value: String) {
+        let key = if key.chars().any(char::is_control) {
+            match self.tag_key_policy {
+                TagKeyPolicy::Sanitize => key
+                    .chars()
+                    .map(|c| if c.is_control() { '_' } else { c })
+                    .collect(),
+                TagKeyPolicy::Reject => return,
+                TagKeyPolicy::Allow => key,
+            }
+        } else {
+            key
+        };
+        self.tags.insert(key, value);
+    }
+
     /// Constructs a `Span`.
     ///
     /// # Panics
@@ -441,3 +863,397 @@ impl Builder {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn annotation_offset() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .timestamp(start)
+            .annotation_offset(Duration::from_millis(5), "ws")
+            .annotation(Annotation::at_offset(
+                start,
+                Duration::from_millis(10),
+                "wr",
+            ))
+            .build();
+
+        let annotations = span.annotations();
+        assert_eq!(annotations[0].timestamp(), start + Duration::from_millis(5));
+        assert_eq!(annotations[0].value(), "ws");
+        assert_eq!(
+            annotations[1].timestamp(),
+            start + Duration::from_millis(10)
+        );
+        assert_eq!(annotations[1].value(), "wr");
+    }
+
+    #[test]
+    fn tag_bytes() {
+        let value = [0, 1, 2, 0xfe, 0xff];
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .tag_bytes("fingerprint", &value)
+            .tag("text", "not base64!")
+            .build();
+
+        assert_eq!(span.tags()["fingerprint"], "AAEC/v8=");
+        assert_eq!(span.tag_bytes("fingerprint"), Some(value.to_vec()));
+        assert_eq!(span.tag_bytes("text"), None);
+        assert_eq!(span.tag_bytes("missing"), None);
+    }
+
+    #[test]
+    fn sort_annotations() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let mut span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .annotation(Annotation::new(start + Duration::from_millis(2), "c"))
+            .annotation(Annotation::new(start + Duration::from_millis(1), "b"))
+            .annotation(Annotation::new(start, "a"))
+            .annotation(Annotation::new(start + Duration::from_millis(1), "a"))
+            .build();
+
+        span.sort_annotations();
+        assert_eq!(
+            span.annotations(),
+            [
+                Annotation::new(start, "a"),
+                Annotation::new(start + Duration::from_millis(1), "a"),
+                Annotation::new(start + Duration::from_millis(1), "b"),
+                Annotation::new(start + Duration::from_millis(2), "c"),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn annotation_offset_without_timestamp() {
+        Span::builder().annotation_offset(Duration::from_millis(5), "ws");
+    }
+
+    #[test]
+    fn tags_sorted() {
+        let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+        let id = SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]);
+        let a = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .tag("c", "3")
+            .tag("a", "1")
+            .tag("b", "2")
+            .build();
+        let b = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .tag("b", "2")
+            .tag("c", "3")
+            .tag("a", "1")
+            .build();
+
+        let expected = [("a", "1"), ("b", "2"), ("c", "3")];
+        assert_eq!(a.tags_sorted().collect::<Vec<_>>(), expected);
+        assert_eq!(b.tags_sorted().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn duration_precision() {
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .duration(Duration::from_nanos(1_500))
+            .build();
+        assert_eq!(span.duration(), Some(Duration::from_nanos(1_500)));
+        assert_eq!(crate::duration_to_micros(span.duration().unwrap()), 1);
+
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .duration(Duration::from_nanos(500))
+            .build();
+        assert_eq!(crate::duration_to_micros(span.duration().unwrap()), 1);
+
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .duration_micros(42)
+            .build();
+        assert_eq!(span.duration(), Some(Duration::from_micros(42)));
+        assert_eq!(crate::duration_to_micros(span.duration().unwrap()), 42);
+    }
+
+    #[test]
+    fn validate() {
+        let builder = || {
+            let mut builder = Span::builder();
+            builder
+                .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+                .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]));
+            builder
+        };
+
+        let client = builder()
+            .kind(Kind::Client)
+            .timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000))
+            .duration(Duration::from_millis(10))
+            .remote_endpoint(Endpoint::builder().service_name("server").build())
+            .build();
+        client.validate().unwrap();
+
+        let err = builder()
+            .kind(Kind::Server)
+            .duration(Duration::from_millis(10))
+            .build()
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "span has a duration but no timestamp");
+
+        let err = builder()
+            .remote_endpoint(Endpoint::builder().service_name("server").build())
+            .build()
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "span has a remote endpoint but no kind");
+    }
+
+    #[test]
+    fn merge() {
+        let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+        let id = SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]);
+        let parent_id = SpanId::from([2, 3, 4, 5, 6, 7, 8, 9]);
+        let client_endpoint = Endpoint::builder().service_name("client").build();
+        let server_endpoint = Endpoint::builder().service_name("server").build();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+
+        let client = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .parent_id(parent_id)
+            .name("get")
+            .kind(Kind::Client)
+            .timestamp(start)
+            .duration(Duration::from_millis(10))
+            .local_endpoint(client_endpoint.clone())
+            .annotation(Annotation::new(start, "ws"))
+            .tag("http.path", "/client")
+            .tag("client", "true")
+            .build();
+        let server = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .parent_id(parent_id)
+            .name("get /foo")
+            .kind(Kind::Server)
+            .shared(true)
+            .local_endpoint(server_endpoint.clone())
+            .remote_endpoint(client_endpoint.clone())
+            .annotation(Annotation::new(start + Duration::from_millis(1), "wr"))
+            .tag("http.path", "/server")
+            .tag("server", "true")
+            .build();
+
+        let merged = client.clone().merge(server.clone()).unwrap();
+        assert_eq!(merged.trace_id(), trace_id);
+        assert_eq!(merged.id(), id);
+        assert_eq!(merged.parent_id(), Some(parent_id));
+        assert_eq!(merged.name(), Some("get"));
+        assert!(matches!(merged.kind(), Some(Kind::Client)));
+        assert_eq!(merged.timestamp(), Some(start));
+        assert_eq!(merged.duration(), Some(Duration::from_millis(10)));
+        assert!(!merged.debug());
+        assert!(!merged.shared());
+        assert_eq!(merged.local_endpoint(), Some(&client_endpoint));
+        assert_eq!(merged.remote_endpoint(), Some(&server_endpoint));
+        let annotations = merged
+            .annotations()
+            .iter()
+            .map(|a| a.value())
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, ["ws", "wr"]);
+        assert_eq!(
+            merged.tags_sorted().collect::<Vec<_>>(),
+            [
+                ("client", "true"),
+                ("http.path", "/client"),
+                ("server", "true")
+            ]
+        );
+
+        let merged = server.merge(client).unwrap();
+        assert_eq!(merged.name(), Some("get /foo"));
+        assert!(matches!(merged.kind(), Some(Kind::Server)));
+        assert_eq!(merged.timestamp(), Some(start));
+        assert!(merged.shared());
+        assert_eq!(merged.local_endpoint(), Some(&server_endpoint));
+        assert_eq!(merged.remote_endpoint(), Some(&client_endpoint));
+    }
+
+    #[test]
+    fn merge_same_kind() {
+        let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+        let id = SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]);
+        let local_endpoint = Endpoint::builder().service_name("local").build();
+        let remote_endpoint = Endpoint::builder().service_name("remote").build();
+
+        let a = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .kind(Kind::Server)
+            .local_endpoint(local_endpoint.clone())
+            .build();
+        let b = Span::builder()
+            .trace_id(trace_id)
+            .id(id)
+            .shared(true)
+            .remote_endpoint(remote_endpoint.clone())
+            .build();
+
+        let merged = a.merge(b).unwrap();
+        assert!(matches!(merged.kind(), Some(Kind::Server)));
+        assert!(merged.shared());
+        assert_eq!(merged.local_endpoint(), Some(&local_endpoint));
+        assert_eq!(merged.remote_endpoint(), Some(&remote_endpoint));
+    }
+
+    #[test]
+    fn merge_mismatched_kinds() {
+        let span = |kind| {
+            Span::builder()
+                .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+                .id(SpanId::from([1; 8]))
+                .kind(kind)
+                .build()
+        };
+
+        let err = span(Kind::Client).merge(span(Kind::Producer)).unwrap_err();
+        assert_eq!(err.to_string(), "merged spans have incompatible kinds");
+    }
+
+    #[test]
+    fn merge_mismatched_ids() {
+        let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+        let span = |trace_id, id| Span::builder().trace_id(trace_id).id(id).build();
+
+        let err = span(trace_id, SpanId::from([1; 8]))
+            .merge(span(trace_id, SpanId::from([2; 8])))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "merged spans have different span IDs");
+
+        let err = span(trace_id, SpanId::from([1; 8]))
+            .merge(span(TraceId::from([1; 8]), SpanId::from([1; 8])))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "merged spans have different trace IDs");
+    }
+
+    #[test]
+    fn tag_key_policy() {
+        let mut span = Span::builder();
+        span.trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .tag("sanitized\nkey", "a")
+            .tag_key_policy(TagKeyPolicy::Reject)
+            .tag("rejected\nkey", "b")
+            .tag("valid key", "c")
+            .tag_key_policy(TagKeyPolicy::Allow)
+            .tag("allowed\nkey", "d");
+
+        assert_eq!(
+            span.build().tags_sorted().collect::<Vec<_>>(),
+            [
+                ("allowed\nkey", "d"),
+                ("sanitized_key", "a"),
+                ("valid key", "c")
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn string_timestamps() {
+        #[derive(serde::Serialize)]
+        struct StringTimestamps(#[serde(serialize_with = "serialize_string_timestamps")] Span);
+
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .name("get")
+            .kind(Kind::Server)
+            .timestamp(SystemTime::UNIX_EPOCH + Duration::from_micros(1_500_000_000_123))
+            .duration(Duration::from_micros(10))
+            .shared(true)
+            .local_endpoint(Endpoint::builder().service_name("server").build())
+            .annotation(Annotation::new(
+                SystemTime::UNIX_EPOCH + Duration::from_micros(1_500_000_000_456),
+                "ws",
+            ))
+            .tag("b", "2")
+            .tag("a", "1")
+            .build();
+
+        let numbers = serde_json::to_value(&span).unwrap();
+        assert_eq!(numbers["timestamp"], 1_500_000_000_123u64);
+        assert_eq!(numbers["annotations"][0]["timestamp"], 1_500_000_000_456u64);
+
+        let strings = serde_json::to_value(StringTimestamps(span)).unwrap();
+        assert_eq!(strings["timestamp"], "1500000000123");
+        assert_eq!(strings["annotations"][0]["timestamp"], "1500000000456");
+
+        let mut expected = numbers;
+        expected["timestamp"] = "1500000000123".into();
+        expected["annotations"][0]["timestamp"] = "1500000000456".into();
+        assert_eq!(strings, expected);
+
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]))
+            .build();
+        assert_eq!(
+            serde_json::to_string(&StringTimestamps(span.clone())).unwrap(),
+            serde_json::to_string(&span).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn time_micros_string() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Event {
+            #[serde(with = "crate::time_micros_string")]
+            timestamp: SystemTime,
+        }
+
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_micros(1_500_000_000_123);
+        let json = serde_json::to_string(&Event { timestamp }).unwrap();
+        assert_eq!(json, r#"{"timestamp":"1500000000123"}"#);
+        let event = serde_json::from_str::<Event>(&json).unwrap();
+        assert_eq!(event.timestamp, timestamp);
+
+        assert!(serde_json::from_str::<Event>(r#"{"timestamp":"soon"}"#).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn deterministic_tags() {
+        let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+        let id = SpanId::from([1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut a = Span::builder();
+        a.trace_id(trace_id).id(id);
+        let mut b = Span::builder();
+        b.trace_id(trace_id).id(id);
+        for i in 0..100 {
+            a.tag(i.to_string(), "a");
+            b.tag((99 - i).to_string(), "a");
+        }
+
+        let a = serde_json::to_string(&a.build()).unwrap();
+        let b = serde_json::to_string(&b.build()).unwrap();
+        assert_eq!(a, b);
+    }
+}
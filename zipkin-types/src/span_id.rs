@@ -21,7 +21,7 @@ use std::str::FromStr;
 /// The ID of a span.
 ///
 /// Span IDs are 8 bytes, and are serialized as hexadecimal strings.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SpanId {
     buf: [u8; 8],
 }
@@ -35,9 +35,9 @@ impl FromStr for SpanId {
             Ok(8) => {
                 HEXLOWER_PERMISSIVE
                     .decode_mut(s.as_bytes(), &mut buf)
-                    .map_err(|e| SpanIdParseError(Some(e.error)))?;
+                    .map_err(|e| SpanIdParseError::new(s, Some(e.error)))?;
             }
-            _ => return Err(SpanIdParseError(None)),
+            _ => return Err(SpanIdParseError::new(s, None)),
         }
 
         Ok(SpanId { buf })
@@ -114,20 +114,75 @@ impl From<[u8; 8]> for SpanId {
 
 /// The error returned when parsing a `SpanId` from a string.
 #[derive(Debug)]
-pub struct SpanIdParseError(Option<DecodeError>);
+pub struct SpanIdParseError {
+    decode: Option<DecodeError>,
+    len: usize,
+}
+
+impl SpanIdParseError {
+    fn new(s: &str, decode: Option<DecodeError>) -> SpanIdParseError {
+        SpanIdParseError {
+            decode,
+            len: s.len(),
+        }
+    }
+
+    /// Returns the length of the input string.
+    #[inline]
+    pub fn input_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the valid lengths of an input string.
+    #[inline]
+    pub fn expected_lens(&self) -> &'static [usize] {
+        &[16]
+    }
+
+    /// Returns the position of the invalid character in the input string, if the input had a valid length.
+    #[inline]
+    pub fn position(&self) -> Option<usize> {
+        self.decode.map(|e| e.position)
+    }
+}
 
 impl fmt::Display for SpanIdParseError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.write_str("error parsing span: ")?;
-        match self.0 {
+        match self.decode {
             Some(ref err) => write!(fmt, "{}", err),
-            None => fmt.write_str("invalid length"),
+            None => write!(fmt, "invalid length {} (expected 16)", self.len),
         }
     }
 }
 
 impl Error for SpanIdParseError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.0.as_ref().map(|e| e as _)
+        self.decode.as_ref().map(|e| e as _)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_error_length() {
+        let err = "0001020304".parse::<SpanId>().unwrap_err();
+        assert_eq!(err.input_len(), 10);
+        assert_eq!(err.expected_lens(), &[16]);
+        assert_eq!(err.position(), None);
+        assert_eq!(
+            err.to_string(),
+            "error parsing span: invalid length 10 (expected 16)"
+        );
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let err = "00010203040506zz".parse::<SpanId>().unwrap_err();
+        assert_eq!(err.input_len(), 16);
+        assert_eq!(err.position(), Some(14));
+        assert!(err.source().is_some());
     }
 }
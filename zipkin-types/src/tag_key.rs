@@ -0,0 +1,102 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! Tag keys.
+use std::fmt;
+
+/// A well-known tag key.
+///
+/// Tag methods accept any `Into<String>` key, so these constants can be used interchangeably with string literals
+/// while avoiding typos in common keys.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TagKey(&'static str);
+
+impl TagKey {
+    /// The `error` tag, set when a span's operation failed. The value is a description of the error.
+    pub const ERROR: TagKey = TagKey("error");
+
+    /// The `http.host` tag, containing the host header of an HTTP request.
+    pub const HTTP_HOST: TagKey = TagKey("http.host");
+
+    /// The `http.method` tag, containing the method of an HTTP request.
+    pub const HTTP_METHOD: TagKey = TagKey("http.method");
+
+    /// The `http.path` tag, containing the absolute path of an HTTP request's URI.
+    pub const HTTP_PATH: TagKey = TagKey("http.path");
+
+    /// The `http.route` tag, containing the route template which matched an HTTP request.
+    pub const HTTP_ROUTE: TagKey = TagKey("http.route");
+
+    /// The `http.url` tag, containing the entire URI of an HTTP request.
+    pub const HTTP_URL: TagKey = TagKey("http.url");
+
+    /// The `http.status_code` tag, containing the status code of an HTTP response.
+    pub const HTTP_STATUS_CODE: TagKey = TagKey("http.status_code");
+
+    /// The `http.request.size` tag, containing the size of an HTTP request body in bytes.
+    pub const HTTP_REQUEST_SIZE: TagKey = TagKey("http.request.size");
+
+    /// The `http.response.size` tag, containing the size of an HTTP response body in bytes.
+    pub const HTTP_RESPONSE_SIZE: TagKey = TagKey("http.response.size");
+
+    /// The `lc` tag, containing the name of the component which performed a local operation.
+    pub const LOCAL_COMPONENT: TagKey = TagKey("lc");
+
+    /// The `sql.query` tag, containing the text of a SQL query.
+    pub const SQL_QUERY: TagKey = TagKey("sql.query");
+
+    /// All of the well-known tag keys defined by this type.
+    ///
+    /// This includes every key set by the convenience methods of the `zipkin` and `http-zipkin` crates, such as
+    /// `OpenSpan::local_component`.
+    pub const ALL: &'static [TagKey] = &[
+        TagKey::ERROR,
+        TagKey::HTTP_HOST,
+        TagKey::HTTP_METHOD,
+        TagKey::HTTP_PATH,
+        TagKey::HTTP_ROUTE,
+        TagKey::HTTP_URL,
+        TagKey::HTTP_STATUS_CODE,
+        TagKey::HTTP_REQUEST_SIZE,
+        TagKey::HTTP_RESPONSE_SIZE,
+        TagKey::LOCAL_COMPONENT,
+        TagKey::SQL_QUERY,
+    ];
+
+    /// Returns the key as a string.
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for TagKey {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.0)
+    }
+}
+
+impl AsRef<str> for TagKey {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl From<TagKey> for String {
+    #[inline]
+    fn from(key: TagKey) -> String {
+        key.0.to_string()
+    }
+}
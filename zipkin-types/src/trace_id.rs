@@ -18,7 +18,7 @@ use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 enum Inner {
     Short([u8; 8]),
     Long([u8; 16]),
@@ -28,7 +28,7 @@ enum Inner {
 ///
 /// Trace IDs are either 8 or 16 bytes, and are serialized as hexadecimal
 /// strings.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TraceId(Inner);
 
 impl fmt::Display for TraceId {
@@ -49,17 +49,17 @@ impl FromStr for TraceId {
                 let mut buf = [0; 8];
                 HEXLOWER_PERMISSIVE
                     .decode_mut(s.as_bytes(), &mut buf)
-                    .map_err(|e| TraceIdParseError(Some(e.error)))?;
+                    .map_err(|e| TraceIdParseError::new(s, Some(e.error)))?;
                 Inner::Short(buf)
             }
             Ok(16) => {
                 let mut buf = [0; 16];
                 HEXLOWER_PERMISSIVE
                     .decode_mut(s.as_bytes(), &mut buf)
-                    .map_err(|e| TraceIdParseError(Some(e.error)))?;
+                    .map_err(|e| TraceIdParseError::new(s, Some(e.error)))?;
                 Inner::Long(buf)
             }
-            _ => return Err(TraceIdParseError(None)),
+            _ => return Err(TraceIdParseError::new(s, None)),
         };
 
         Ok(TraceId(inner))
@@ -119,6 +119,37 @@ impl TraceId {
             Inner::Long(ref buf) => buf,
         }
     }
+
+    /// Returns the 8 byte form of the trace ID.
+    ///
+    /// 16 byte trace IDs are truncated to their lower 8 bytes, which is how Zipkin identifies a 16 byte trace with
+    /// its 8 byte form.
+    #[inline]
+    pub fn to_short(self) -> TraceId {
+        match self.0 {
+            Inner::Short(_) => self,
+            Inner::Long(buf) => {
+                let mut short = [0; 8];
+                short.copy_from_slice(&buf[8..]);
+                TraceId(Inner::Short(short))
+            }
+        }
+    }
+
+    /// Returns the 16 byte form of the trace ID.
+    ///
+    /// 8 byte trace IDs are extended with upper bytes of zero.
+    #[inline]
+    pub fn to_long(self) -> TraceId {
+        match self.0 {
+            Inner::Short(buf) => {
+                let mut long = [0; 16];
+                long[8..].copy_from_slice(&buf);
+                TraceId(Inner::Long(long))
+            }
+            Inner::Long(_) => self,
+        }
+    }
 }
 
 impl From<[u8; 8]> for TraceId {
@@ -137,20 +168,75 @@ impl From<[u8; 16]> for TraceId {
 
 /// The error returned when parsing a `TraceId` from a string.
 #[derive(Debug)]
-pub struct TraceIdParseError(Option<DecodeError>);
+pub struct TraceIdParseError {
+    decode: Option<DecodeError>,
+    len: usize,
+}
+
+impl TraceIdParseError {
+    fn new(s: &str, decode: Option<DecodeError>) -> TraceIdParseError {
+        TraceIdParseError {
+            decode,
+            len: s.len(),
+        }
+    }
+
+    /// Returns the length of the input string.
+    #[inline]
+    pub fn input_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the valid lengths of an input string.
+    #[inline]
+    pub fn expected_lens(&self) -> &'static [usize] {
+        &[16, 32]
+    }
+
+    /// Returns the position of the invalid character in the input string, if the input had a valid length.
+    #[inline]
+    pub fn position(&self) -> Option<usize> {
+        self.decode.map(|e| e.position)
+    }
+}
 
 impl fmt::Display for TraceIdParseError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.write_str("error parsing trace ID: ")?;
-        match self.0 {
+        match self.decode {
             Some(ref err) => write!(fmt, "{}", err),
-            None => fmt.write_str("invalid length"),
+            None => write!(fmt, "invalid length {} (expected 16 or 32)", self.len),
         }
     }
 }
 
 impl Error for TraceIdParseError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.0.as_ref().map(|e| e as _)
+        self.decode.as_ref().map(|e| e as _)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_error_length() {
+        let err = "0001020304".parse::<TraceId>().unwrap_err();
+        assert_eq!(err.input_len(), 10);
+        assert_eq!(err.expected_lens(), &[16, 32]);
+        assert_eq!(err.position(), None);
+        assert_eq!(
+            err.to_string(),
+            "error parsing trace ID: invalid length 10 (expected 16 or 32)"
+        );
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let err = "00010203040506zz".parse::<TraceId>().unwrap_err();
+        assert_eq!(err.input_len(), 16);
+        assert_eq!(err.position(), Some(14));
+        assert!(err.source().is_some());
     }
 }
@@ -0,0 +1,53 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+//! Clocks.
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A clock provides the current time to the tracer.
+///
+/// The tracer uses the clock to compute the durations of spans. The default `SystemClock` uses the real time, but
+/// other implementations can be used to, for example, produce deterministic durations in tests.
+pub trait Clock {
+    /// Returns the current instant.
+    fn now_instant(&self) -> Instant;
+}
+
+impl<T> Clock for Arc<T>
+where
+    T: ?Sized + Clock,
+{
+    fn now_instant(&self) -> Instant {
+        (**self).now_instant()
+    }
+}
+
+impl<T> Clock for Box<T>
+where
+    T: ?Sized + Clock,
+{
+    fn now_instant(&self) -> Instant {
+        (**self).now_instant()
+    }
+}
+
+/// A `Clock` which uses the system's monotonic clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -1,16 +1,29 @@
 use crate::TraceContext;
-use std::cell::Cell;
+use pin_project_lite::pin_project;
+use std::cell::RefCell;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+
+struct Entry {
+    context: Option<TraceContext>,
+    live: bool,
+}
 
 thread_local! {
-    static CURRENT: Cell<Option<TraceContext>> = Cell::new(None);
+    static CURRENT: RefCell<Vec<Entry>> = const { RefCell::new(vec![]) };
 }
 
 /// A guard object for the thread-local current trace context.
 ///
 /// It will restore the previous trace context when it drops.
+///
+/// Guards form a stack. If a guard is dropped while a guard created after it is still live, its context remains
+/// hidden beneath the newer guard's until that guard drops, at which point both are removed.
 pub struct CurrentGuard {
-    prev: Option<TraceContext>,
+    index: usize,
     // make sure this type is !Send since it pokes at thread locals
     _p: PhantomData<*const ()>,
 }
@@ -19,7 +32,20 @@ unsafe impl Sync for CurrentGuard {}
 
 impl Drop for CurrentGuard {
     fn drop(&mut self) {
-        CURRENT.with(|c| c.set(self.prev));
+        // the guard may be dropped from another thread local's destructor after CURRENT has been destroyed, in which
+        // case there's nothing left to restore
+        let _ = CURRENT.try_with(|c| {
+            let mut stack = c.borrow_mut();
+            if self.index + 1 == stack.len() {
+                stack.pop();
+                // clean up entries whose guards were dropped out of order
+                while matches!(stack.last(), Some(e) if !e.live) {
+                    stack.pop();
+                }
+            } else {
+                stack[self.index].live = false;
+            }
+        });
     }
 }
 
@@ -31,13 +57,95 @@ impl Drop for CurrentGuard {
 /// A guard object is returned which will restore the previous trace context
 /// when it falls out of scope.
 pub fn set_current(context: TraceContext) -> CurrentGuard {
+    replace_current(Some(context))
+}
+
+/// Clears this thread's current trace context.
+///
+/// A guard object is returned which will restore the previous trace context when it falls out of scope. This can be
+/// used in worker threads which run independent jobs to ensure a stale context isn't inherited by a job.
+pub fn clear_current() -> CurrentGuard {
+    replace_current(None)
+}
+
+fn replace_current(context: Option<TraceContext>) -> CurrentGuard {
+    // if CURRENT has been destroyed, the guard's drop won't be able to access it either, so the index is never used
+    let index = CURRENT
+        .try_with(|c| {
+            let mut stack = c.borrow_mut();
+            stack.push(Entry {
+                context,
+                live: true,
+            });
+            stack.len() - 1
+        })
+        .unwrap_or(usize::MAX);
+
     CurrentGuard {
-        prev: CURRENT.with(|c| c.replace(Some(context))),
+        index,
         _p: PhantomData,
     }
 }
 
 /// Returns this thread's current trace context.
 pub fn current() -> Option<TraceContext> {
-    CURRENT.with(|c| c.get())
+    CURRENT
+        .try_with(|c| c.borrow().last().and_then(|e| e.context))
+        .ok()
+        .flatten()
+}
+
+/// Wraps a future, propagating this thread's current trace context into it.
+///
+/// The current trace context at the time this function is called is captured, and set as the current whenever the
+/// returned future is polled. Unlike `OpenSpan::bind`, this does not open a span.
+pub fn instrument<F>(future: F) -> Instrumented<F>
+where
+    F: Future,
+{
+    Instrumented {
+        context: current(),
+        future,
+    }
+}
+
+/// Spawns a new thread, propagating this thread's current trace context into it.
+///
+/// This is a wrapper around `std::thread::spawn` which captures the current trace context at the time this function
+/// is called, and sets it as the current in the new thread while the closure runs. Like `instrument`, this does not
+/// open a span.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let context = current();
+    thread::spawn(move || {
+        let _guard = replace_current(context);
+        f()
+    })
+}
+
+pin_project! {
+    /// A future which sets a trace context as the current whenever it's polled.
+    ///
+    /// It is created by the `instrument` function.
+    pub struct Instrumented<F> {
+        context: Option<TraceContext>,
+        #[pin]
+        future: F,
+    }
+}
+
+impl<F> Future for Instrumented<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.project();
+        let _guard = replace_current(*this.context);
+        this.future.poll(cx)
+    }
 }
@@ -22,6 +22,9 @@
 //! If the `serde` Cargo feature is enabled, `Annotation`, `Endpoint`, `Kind`, `Span`, `SpanId`, and
 //! `TraceId` implement `Serialize` and `Deserialize` in the standard Zipkin format.
 //!
+//! If the `json` Cargo feature is enabled, the `json` module provides a lightweight encoder for spans in the standard
+//! Zipkin JSON format which does not depend on `serde`.
+//!
 //! [Zipkin]: http://zipkin.io/
 //! [specification]: https://github.com/openzipkin/zipkin-api/blob/master/zipkin2-api.yaml
 #![doc(html_root_url = "https://docs.rs/zipkin/0.4")]
@@ -29,12 +32,17 @@
 
 #[cfg(feature = "macros")]
 pub use zipkin_macros::*;
+#[cfg(feature = "json")]
+#[doc(inline)]
+pub use zipkin_types::json;
 #[doc(inline)]
 pub use zipkin_types::{
-    annotation, endpoint, span, span_id, trace_id, Annotation, Endpoint, Kind, Span, SpanId,
-    TraceId,
+    annotation, endpoint, span, span_id, tag_key, trace_id, Annotation, Endpoint, Kind, Span,
+    SpanId, TagKey, TagKeyPolicy, TraceId,
 };
 
+#[doc(inline)]
+pub use crate::clock::Clock;
 #[doc(inline)]
 pub use crate::current::*;
 #[doc(inline)]
@@ -50,6 +58,7 @@ pub use crate::trace_context::TraceContext;
 #[doc(inline)]
 pub use crate::tracer::*;
 
+pub mod clock;
 mod current;
 mod open_span;
 pub mod report;
@@ -1,5 +1,7 @@
-use crate::{span, tracer, Annotation, CurrentGuard, Endpoint, Kind, TraceContext};
+use crate::tracer::Tracer;
+use crate::{span, tracer, Annotation, CurrentGuard, Endpoint, Kind, Span, TagKey, TraceContext};
 use pin_project_lite::pin_project;
+use std::fmt::{self, Display};
 use std::future::Future;
 use std::mem;
 use std::pin::Pin;
@@ -7,9 +9,16 @@ use std::task::{Context, Poll};
 use std::time::Instant;
 
 /// A type indicating that an `OpenSpan` is "attached" to the current thread.
-pub struct Attached(CurrentGuard);
+pub struct Attached(#[allow(dead_code)] CurrentGuard);
+
+impl fmt::Debug for Attached {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("Attached")
+    }
+}
 
 /// A type indicating that an `OpenSpan` is "detached" from the current thread.
+#[derive(Debug)]
 pub struct Detached(());
 
 #[allow(clippy::large_enum_variant)]
@@ -43,6 +52,19 @@ pub struct OpenSpan<T> {
     state: SpanState,
 }
 
+impl<T> fmt::Debug for OpenSpan<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("OpenSpan")
+            .field("mode", &self._mode)
+            .field("context", &self.context)
+            .field("recording", &self.is_recording())
+            .finish()
+    }
+}
+
 impl<T> Drop for OpenSpan<T> {
     fn drop(&mut self) {
         if let SpanState::Real {
@@ -51,13 +73,24 @@ impl<T> Drop for OpenSpan<T> {
         } = &mut self.state
         {
             if let Some(tracer) = tracer::TRACER.borrow() {
-                let span = span.duration(start_instant.elapsed()).build();
-                tracer.reporter.report(span);
+                let span = finish(span, *start_instant, tracer);
+                let too_short = matches!(span.duration(), Some(d) if d < tracer.min_duration);
+                if !too_short || span.debug() || self.context.debug() {
+                    tracer.reporter.report(span);
+                }
             }
         }
     }
 }
 
+fn finish(span: &mut span::Builder, start_instant: Instant, tracer: &Tracer) -> Span {
+    let duration = tracer
+        .clock
+        .now_instant()
+        .saturating_duration_since(start_instant);
+    span.duration(duration).build()
+}
+
 impl<T> OpenSpan<T> {
     /// Returns the context associated with this span.
     #[inline]
@@ -65,17 +98,48 @@ impl<T> OpenSpan<T> {
         self.context
     }
 
+    /// Returns a new context for a child of this span.
+    ///
+    /// The context has a new span ID, and this span as its parent, but no span is started for it. This can be used to
+    /// hand out contexts to separate tasks which should all be children of this span.
+    #[inline]
+    pub fn child_context(&self) -> TraceContext {
+        tracer::child_context(self.context)
+    }
+
+    /// Determines if this span is being recorded.
+    ///
+    /// Modifications to a span which is not being recorded, like adding tags or annotations, have no effect.
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        match self.state {
+            SpanState::Real { .. } => true,
+            SpanState::Nop => false,
+        }
+    }
+
     /// Sets the name of this span.
+    ///
+    /// The name is normalized according to the tracer's `NamePolicy`.
     #[inline]
-    pub fn name(&mut self, name: &str) {
+    pub fn name<N>(&mut self, name: N)
+    where
+        N: Into<String>,
+    {
         if let SpanState::Real { span, .. } = &mut self.state {
-            span.name(name);
+            match tracer::TRACER.borrow() {
+                Some(tracer) => span.name(tracer.name_policy.apply(name.into())),
+                None => span.name(name),
+            };
         }
     }
 
     /// A builder-style version of `name`.
     #[inline]
-    pub fn with_name(mut self, name: &str) -> OpenSpan<T> {
+    pub fn with_name<N>(mut self, name: N) -> OpenSpan<T>
+    where
+        N: Into<String>,
+    {
         self.name(name);
         self
     }
@@ -95,6 +159,40 @@ impl<T> OpenSpan<T> {
         self
     }
 
+    /// Sets the local endpoint of this span.
+    ///
+    /// Defaults to the local endpoint of the tracer.
+    #[inline]
+    pub fn local_endpoint(&mut self, local_endpoint: Endpoint) {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.local_endpoint(local_endpoint);
+        }
+    }
+
+    /// A builder-style version of `local_endpoint`.
+    #[inline]
+    pub fn with_local_endpoint(mut self, local_endpoint: Endpoint) -> OpenSpan<T> {
+        self.local_endpoint(local_endpoint);
+        self
+    }
+
+    /// Sets the debug flag of the reported span.
+    ///
+    /// This only affects the reported span, not the sampling flags propagated to child spans.
+    #[inline]
+    pub fn set_debug(&mut self, debug: bool) {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.debug(debug);
+        }
+    }
+
+    /// A builder-style version of `set_debug`.
+    #[inline]
+    pub fn with_debug(mut self, debug: bool) -> OpenSpan<T> {
+        self.set_debug(debug);
+        self
+    }
+
     /// Sets the remote endpoint of this span.
     #[inline]
     pub fn remote_endpoint(&mut self, remote_endpoint: Endpoint) {
@@ -126,9 +224,32 @@ impl<T> OpenSpan<T> {
         self
     }
 
+    /// Returns the annotations attached to this span.
+    ///
+    /// The annotations of a span which is not being recorded are always empty.
+    #[inline]
+    pub fn annotations(&self) -> &[Annotation] {
+        match &self.state {
+            SpanState::Real { span, .. } => span.get_annotations(),
+            SpanState::Nop => &[],
+        }
+    }
+
+    /// Removes all annotations from this span.
+    #[inline]
+    pub fn clear_annotations(&mut self) {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.clear_annotations();
+        }
+    }
+
     /// Attaches a tag to this span.
     #[inline]
-    pub fn tag(&mut self, key: &str, value: &str) {
+    pub fn tag<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
         if let SpanState::Real { span, .. } = &mut self.state {
             span.tag(key, value);
         }
@@ -136,10 +257,150 @@ impl<T> OpenSpan<T> {
 
     /// A builder-style version of `tag`.
     #[inline]
-    pub fn with_tag(mut self, key: &str, value: &str) -> OpenSpan<T> {
+    pub fn with_tag<K, V>(mut self, key: K, value: V) -> OpenSpan<T>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
         self.tag(key, value);
         self
     }
+
+    /// Attaches multiple tags to this span.
+    #[inline]
+    pub fn tags<I>(&mut self, tags: I)
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.tags(tags);
+        }
+    }
+
+    /// A builder-style version of `tags`.
+    #[inline]
+    pub fn with_tags<I>(mut self, tags: I) -> OpenSpan<T>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        self.tags(tags);
+        self
+    }
+
+    /// Attaches a tag to this span, formatting its value with its `Display` implementation.
+    #[inline]
+    pub fn tag_value<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<String>,
+        V: Display,
+    {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.tag_value(key, value);
+        }
+    }
+
+    /// A builder-style version of `tag_value`.
+    #[inline]
+    pub fn with_tag_value<K, V>(mut self, key: K, value: V) -> OpenSpan<T>
+    where
+        K: Into<String>,
+        V: Display,
+    {
+        self.tag_value(key, value);
+        self
+    }
+
+    /// Attaches a tag with a binary value to this span.
+    ///
+    /// The value is encoded with standard base64, which increases its size by a third. `Span::tag_bytes` can be used
+    /// to decode it from the reported span.
+    #[inline]
+    pub fn tag_bytes<K>(&mut self, key: K, value: &[u8])
+    where
+        K: Into<String>,
+    {
+        if let SpanState::Real { span, .. } = &mut self.state {
+            span.tag_bytes(key, value);
+        }
+    }
+
+    /// A builder-style version of `tag_bytes`.
+    #[inline]
+    pub fn with_tag_bytes<K>(mut self, key: K, value: &[u8]) -> OpenSpan<T>
+    where
+        K: Into<String>,
+    {
+        self.tag_bytes(key, value);
+        self
+    }
+
+    /// Sets the local component of this span.
+    ///
+    /// This sets the standard `lc` tag, which identifies the library or component that recorded a span which does
+    /// not involve a remote service.
+    #[inline]
+    pub fn local_component(&mut self, component: &str) {
+        self.tag(TagKey::LOCAL_COMPONENT, component);
+    }
+
+    /// A builder-style version of `local_component`.
+    #[inline]
+    pub fn with_local_component(mut self, component: &str) -> OpenSpan<T> {
+        self.local_component(component);
+        self
+    }
+
+    /// Tags this span with the method and path of an HTTP request.
+    ///
+    /// This sets the standard `http.method` and `http.path` tags. The span's kind is not changed, since it depends
+    /// on whether the span represents the client or server side of the request.
+    #[inline]
+    pub fn http_request(&mut self, method: &str, path: &str) {
+        self.tag(TagKey::HTTP_METHOD, method);
+        self.tag(TagKey::HTTP_PATH, path);
+    }
+
+    /// A builder-style version of `http_request`.
+    #[inline]
+    pub fn with_http_request(mut self, method: &str, path: &str) -> OpenSpan<T> {
+        self.http_request(method, path);
+        self
+    }
+
+    /// Tags this span with the status code of an HTTP response.
+    ///
+    /// This sets the standard `http.status_code` tag, and also the `error` tag if the status indicates a client or
+    /// server error.
+    #[inline]
+    pub fn http_response(&mut self, status: u16) {
+        self.tag_value(TagKey::HTTP_STATUS_CODE, status);
+        if (400..600).contains(&status) {
+            self.tag_value(TagKey::ERROR, status);
+        }
+    }
+
+    /// A builder-style version of `http_response`.
+    #[inline]
+    pub fn with_http_response(mut self, status: u16) -> OpenSpan<T> {
+        self.http_response(status);
+        self
+    }
+
+    /// Finishes the span, returning it rather than reporting it.
+    ///
+    /// Returns `None` if the span is not being recorded.
+    pub fn finish(mut self) -> Option<Span> {
+        // since we've swapped in Nop here, self's Drop impl won't do anything
+        match mem::replace(&mut self.state, SpanState::Nop) {
+            SpanState::Real {
+                mut span,
+                start_instant,
+            } => tracer::TRACER
+                .borrow()
+                .map(|tracer| finish(&mut span, start_instant, tracer)),
+            SpanState::Nop => None,
+        }
+    }
 }
 
 impl OpenSpan<Attached> {
@@ -165,6 +426,15 @@ impl OpenSpan<Attached> {
 }
 
 impl OpenSpan<Detached> {
+    #[inline]
+    pub(crate) fn new_detached(context: TraceContext, state: SpanState) -> OpenSpan<Detached> {
+        OpenSpan {
+            _mode: Detached(()),
+            context,
+            state,
+        }
+    }
+
     /// Re-attaches this span's context to the tracer.
     #[inline]
     pub fn attach(mut self) -> OpenSpan<Attached> {
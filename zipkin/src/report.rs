@@ -13,9 +13,28 @@
 //  limitations under the License.
 
 //! Span reporters.
+#[cfg(feature = "blocking-reporter")]
+use crate::json::TimestampEncoding;
 use crate::Span;
 use log::info;
-use std::sync::Arc;
+#[cfg(feature = "json")]
+use log::warn;
+#[cfg(feature = "blocking-reporter")]
+use std::collections::HashSet;
+#[cfg(feature = "blocking-reporter")]
+use std::convert::TryFrom;
+#[cfg(feature = "json")]
+use std::io;
+use std::mem;
+#[cfg(feature = "json")]
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+#[cfg(feature = "blocking-reporter")]
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "blocking-reporter")]
+use std::thread::{self, JoinHandle};
+#[cfg(feature = "blocking-reporter")]
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// A reporter consumes Zipkin spans and reports them.
 ///
@@ -24,6 +43,36 @@ use std::sync::Arc;
 pub trait Report {
     /// Reports a span.
     fn report(&self, span: Span);
+
+    /// Reports a batch of spans.
+    ///
+    /// The default implementation simply calls `report` on each span. Reporters which can handle multiple spans more
+    /// efficiently than one at a time should override it.
+    fn report_batch(&self, spans: Vec<Span>) {
+        for span in spans {
+            self.report(span);
+        }
+    }
+
+    /// Reports a span shared with other reporters.
+    ///
+    /// This allows a span to be passed to multiple reporters without copying it for each of them. Reporters which
+    /// only need to read the span, for example to serialize it, should override this method to avoid the copy.
+    ///
+    /// The default implementation calls `report` with the span, which has to be cloned unless this is the last
+    /// reference to it.
+    fn report_arc(&self, span: Arc<Span>) {
+        self.report(Arc::try_unwrap(span).unwrap_or_else(|span| (*span).clone()));
+    }
+
+    /// Flushes any buffered spans and shuts down the reporter.
+    ///
+    /// The global tracer is never dropped, so this method is not called automatically. To shut down a reporter
+    /// installed in the global tracer, retain a handle to it (for example, by installing an `Arc` of the reporter)
+    /// and call this method at the end of the program.
+    ///
+    /// The default implementation does nothing.
+    fn shutdown(&self) {}
 }
 
 impl<T> Report for Arc<T>
@@ -33,6 +82,18 @@ where
     fn report(&self, span: Span) {
         (**self).report(span)
     }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        (**self).report_batch(spans)
+    }
+
+    fn report_arc(&self, span: Arc<Span>) {
+        (**self).report_arc(span)
+    }
+
+    fn shutdown(&self) {
+        (**self).shutdown()
+    }
 }
 
 impl<T> Report for Box<T>
@@ -42,6 +103,18 @@ where
     fn report(&self, span: Span) {
         (**self).report(span)
     }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        (**self).report_batch(spans)
+    }
+
+    fn report_arc(&self, span: Arc<Span>) {
+        (**self).report_arc(span)
+    }
+
+    fn shutdown(&self) {
+        (**self).shutdown()
+    }
 }
 
 /// A `Report`er which does nothing.
@@ -62,3 +135,584 @@ impl Report for LoggingReporter {
         info!("{:?}", span);
     }
 }
+
+/// A `Report`er which transforms spans before passing them to another.
+///
+/// The function can modify a span, for example to redact sensitive tag values, or return `None` to drop it.
+pub struct MapReporter<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> MapReporter<R, F>
+where
+    F: Fn(Span) -> Option<Span>,
+{
+    /// Creates a new `MapReporter` which applies a function to spans before reporting them to `inner`.
+    pub fn new(inner: R, f: F) -> MapReporter<R, F> {
+        MapReporter { inner, f }
+    }
+
+    /// Returns a shared reference to the inner reporter.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R, F> Report for MapReporter<R, F>
+where
+    R: Report,
+    F: Fn(Span) -> Option<Span>,
+{
+    fn report(&self, span: Span) {
+        if let Some(span) = (self.f)(span) {
+            self.inner.report(span);
+        }
+    }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        let spans = spans.into_iter().filter_map(&self.f).collect::<Vec<_>>();
+        if !spans.is_empty() {
+            self.inner.report_batch(spans);
+        }
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A `Report`er which decides whether to pass spans to another after they finish.
+///
+/// This allows spans to be filtered based on their outcome, for example to keep only spans which failed or were slow.
+/// Note that the decision is made independently for each span, not for each trace. Spans are reported as they finish,
+/// so a trace's other spans (which may be in other processes) are not available when the decision is made. A trace
+/// whose spans are filtered this way will generally be incomplete: a kept span's parent or children may have been
+/// dropped. Spans are only considered if they were sampled by the tracer in the first place.
+pub struct TailSampleReporter<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> TailSampleReporter<R, F>
+where
+    F: Fn(&Span) -> bool,
+{
+    /// Creates a new `TailSampleReporter` which reports spans to `inner` if `f` returns `true` for them.
+    pub fn new(inner: R, f: F) -> TailSampleReporter<R, F> {
+        TailSampleReporter { inner, f }
+    }
+
+    /// Returns a shared reference to the inner reporter.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R, F> Report for TailSampleReporter<R, F>
+where
+    R: Report,
+    F: Fn(&Span) -> bool,
+{
+    fn report(&self, span: Span) {
+        if (self.f)(&span) {
+            self.inner.report(span);
+        }
+    }
+
+    fn report_batch(&self, mut spans: Vec<Span>) {
+        spans.retain(&self.f);
+        if !spans.is_empty() {
+            self.inner.report_batch(spans);
+        }
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+}
+
+/// A `Report`er which buffers spans until they are explicitly flushed.
+///
+/// Spans are held in memory until `flush` is called, which passes them to the inner reporter as a single batch on the
+/// calling thread. No background threads are used, so this gives the application full control over when I/O
+/// happens, for example in single-threaded runtimes. The buffer is unbounded, so `flush` should be called
+/// regularly.
+pub struct ManualReporter<R> {
+    inner: R,
+    buf: Mutex<Vec<Span>>,
+}
+
+impl<R> ManualReporter<R>
+where
+    R: Report,
+{
+    /// Creates a new `ManualReporter` which reports spans to `inner` when flushed.
+    pub fn new(inner: R) -> ManualReporter<R> {
+        ManualReporter {
+            inner,
+            buf: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns a shared reference to the inner reporter.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reports all buffered spans to the inner reporter, blocking until it returns.
+    ///
+    /// Does nothing if no spans are buffered.
+    pub fn flush(&self) {
+        let spans = mem::take(&mut *self.buf.lock().unwrap());
+        if !spans.is_empty() {
+            self.inner.report_batch(spans);
+        }
+    }
+}
+
+impl<R> Report for ManualReporter<R>
+where
+    R: Report,
+{
+    fn report(&self, span: Span) {
+        self.buf.lock().unwrap().push(span);
+    }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        self.buf.lock().unwrap().extend(spans);
+    }
+
+    fn shutdown(&self) {
+        self.flush();
+        self.inner.shutdown()
+    }
+}
+
+/// A `Report`er which emits spans as structured `tracing` events.
+///
+/// Each span is emitted as an `info` level event with the target `zipkin`, and fields `trace_id`, `span_id`,
+/// `parent_id`, `name`, `duration_us`, and `tags`. The `tags` field is the `Debug` representation of the span's
+/// tags, sorted by key.
+///
+/// Requires the `tracing` Cargo feature.
+#[cfg(feature = "tracing")]
+pub struct TracingReporter;
+
+#[cfg(feature = "tracing")]
+impl Report for TracingReporter {
+    fn report(&self, span: Span) {
+        tracing::info!(
+            target: "zipkin",
+            trace_id = %span.trace_id(),
+            span_id = %span.id(),
+            parent_id = span.parent_id().map(tracing::field::display),
+            name = span.name(),
+            duration_us = span.duration().map(|d| d.as_micros() as u64),
+            tags = ?span.tags_sorted().collect::<Vec<_>>(),
+            "span finished",
+        );
+    }
+}
+
+/// The maximum size of the payload of a UDP datagram sent by `UdpReporter`.
+#[cfg(feature = "json")]
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// A `Report`er which sends spans over UDP to a collector.
+///
+/// Each span is sent as a single datagram containing a JSON-encoded list of spans in the standard Zipkin format. A
+/// batch of spans reported at once via `report_batch` is sent in a single datagram if it fits.
+///
+/// UDP datagrams are limited in size to `MAX_DATAGRAM_SIZE` bytes, and further limited in practice by the MTU of the
+/// network. Spans which do not fit in a datagram are dropped with a warning, as are spans which fail to send.
+///
+/// Requires the `json` Cargo feature.
+#[cfg(feature = "json")]
+pub struct UdpReporter {
+    socket: UdpSocket,
+}
+
+#[cfg(feature = "json")]
+impl UdpReporter {
+    /// Creates a new `UdpReporter` sending spans to the specified address.
+    ///
+    /// If the address resolves to multiple socket addresses, the first is used.
+    pub fn new<A>(addr: A) -> io::Result<UdpReporter>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "address resolved to no socket addresses",
+            )
+        })?;
+
+        let local_addr = match addr {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(addr)?;
+
+        Ok(UdpReporter { socket })
+    }
+
+    fn send(&self, spans: &[Span]) -> bool {
+        let mut buf = vec![];
+        crate::json::encode_spans(spans, &mut buf);
+        if buf.len() > MAX_DATAGRAM_SIZE {
+            return false;
+        }
+
+        if let Err(e) = self.socket.send(&buf) {
+            warn!("error sending spans: {}", e);
+        }
+        true
+    }
+}
+
+#[cfg(feature = "json")]
+impl Report for UdpReporter {
+    fn report(&self, span: Span) {
+        if !self.send(&[span]) {
+            warn!("dropping span larger than the maximum datagram size");
+        }
+    }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        if !self.send(&spans) {
+            for span in spans {
+                self.report(span);
+            }
+        }
+    }
+}
+
+/// A `Report`er which sends spans to a collector's HTTP API without an async runtime.
+///
+/// Reported spans are queued and sent by a background thread as JSON-encoded batches in POST requests. A batch is
+/// sent once it reaches the configured size, or once the flush interval elapses. Spans reported while the queue is
+/// full are dropped with a warning, as are batches which fail to send.
+///
+/// If the collector rejects a batch with a 429 or 503 status and a `Retry-After` header, the reporter waits for the
+/// requested delay (up to 60 seconds) and then retries the batch once. No other requests are made while waiting.
+///
+/// Fallback collectors can be configured with `BlockingReporterBuilder::add_url`.
+///
+/// The `shutdown` method sends any queued spans and stops the background thread. Dropping the reporter does the
+/// same, but without waiting for the background thread to finish.
+///
+/// Requires the `blocking-reporter` Cargo feature.
+#[cfg(feature = "blocking-reporter")]
+pub struct BlockingReporter {
+    sender: SyncSender<Option<Span>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[cfg(feature = "blocking-reporter")]
+impl BlockingReporter {
+    /// Creates a new `BlockingReporter` sending spans to the specified URL with the default configuration.
+    ///
+    /// The URL should be that of the collector's span endpoint, for example `http://localhost:9411/api/v2/spans`.
+    /// Both `http` and `https` URLs are supported.
+    pub fn new(url: &str) -> io::Result<BlockingReporter> {
+        BlockingReporter::builder(url).build()
+    }
+
+    /// Returns a builder used to configure a `BlockingReporter`.
+    pub fn builder(url: &str) -> BlockingReporterBuilder {
+        BlockingReporterBuilder {
+            urls: vec![url.to_string()],
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            queue_capacity: 1000,
+            timeout: Duration::from_secs(10),
+            dedupe: false,
+            timestamp_encoding: TimestampEncoding::default(),
+            #[cfg(feature = "debug-hooks")]
+            on_request: None,
+        }
+    }
+}
+
+#[cfg(feature = "blocking-reporter")]
+impl Report for BlockingReporter {
+    fn report(&self, span: Span) {
+        match self.sender.try_send(Some(span)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => warn!("dropping span: reporter queue is full"),
+            Err(TrySendError::Disconnected(_)) => warn!("dropping span: reporter is shut down"),
+        }
+    }
+
+    fn shutdown(&self) {
+        let worker = match self.worker.lock().unwrap().take() {
+            Some(worker) => worker,
+            None => return,
+        };
+
+        // a `None` tells the worker to shut down. The queue may be full, so this needs to be a blocking send
+        let _ = self.sender.send(None);
+        let _ = worker.join();
+    }
+}
+
+/// A builder used to configure a `BlockingReporter`.
+#[cfg(feature = "blocking-reporter")]
+pub struct BlockingReporterBuilder {
+    urls: Vec<String>,
+    batch_size: usize,
+    flush_interval: Duration,
+    queue_capacity: usize,
+    timeout: Duration,
+    dedupe: bool,
+    timestamp_encoding: TimestampEncoding,
+    #[cfg(feature = "debug-hooks")]
+    on_request: Option<RequestHook>,
+}
+
+#[cfg(feature = "debug-hooks")]
+type RequestHook = Box<dyn Fn(&[u8]) + Send + Sync>;
+
+#[cfg(feature = "blocking-reporter")]
+impl BlockingReporterBuilder {
+    /// Adds a fallback collector URL.
+    ///
+    /// Each batch is first sent to the URL the builder was created with. If the request fails without a response,
+    /// for example because the host can't be resolved or a connection to it can't be made, the batch is sent to the
+    /// fallback URLs in the order they were added until one succeeds. This is failover, not load balancing: every
+    /// batch starts over at the first URL, and a collector which responds with an error status is not failed over
+    /// from.
+    pub fn add_url(mut self, url: &str) -> BlockingReporterBuilder {
+        self.urls.push(url.to_string());
+        self
+    }
+
+    /// Sets the maximum number of spans sent in a single request.
+    ///
+    /// Defaults to 100.
+    pub fn batch_size(mut self, batch_size: usize) -> BlockingReporterBuilder {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the maximum amount of time a span is queued before it's sent.
+    ///
+    /// Defaults to 1 second.
+    pub fn flush_interval(mut self, flush_interval: Duration) -> BlockingReporterBuilder {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Sets the maximum number of spans waiting to be sent.
+    ///
+    /// Defaults to 1000.
+    pub fn queue_capacity(mut self, queue_capacity: usize) -> BlockingReporterBuilder {
+        self.queue_capacity = queue_capacity;
+        self
+    }
+
+    /// Sets the timeout of requests to the collector.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> BlockingReporterBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// If enabled, spans with the same trace ID and span ID as a span earlier in the same request are dropped.
+    ///
+    /// This is a best-effort mechanism to handle spans which are reported multiple times in quick succession, for
+    /// example by retried instrumentation. It only considers the spans in a single request, so duplicates reported
+    /// far enough apart will both be sent.
+    ///
+    /// Defaults to `false`.
+    pub fn dedupe(mut self, dedupe: bool) -> BlockingReporterBuilder {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Sets the JSON representation of span and annotation timestamps.
+    ///
+    /// Defaults to `TimestampEncoding::Number`.
+    pub fn timestamp_encoding(
+        mut self,
+        timestamp_encoding: TimestampEncoding,
+    ) -> BlockingReporterBuilder {
+        self.timestamp_encoding = timestamp_encoding;
+        self
+    }
+
+    /// Sets a function called with the body of each request before it's sent to the collector.
+    ///
+    /// This is intended for debugging, for example to capture the exact JSON sent to a collector which is rejecting
+    /// it. The function is called once per batch on the reporter's background thread, before any retries.
+    ///
+    /// Requires the `debug-hooks` Cargo feature.
+    #[cfg(feature = "debug-hooks")]
+    pub fn on_request<F>(mut self, on_request: F) -> BlockingReporterBuilder
+    where
+        F: Fn(&[u8]) + 'static + Send + Sync,
+    {
+        self.on_request = Some(Box::new(on_request));
+        self
+    }
+
+    /// Creates the `BlockingReporter`, starting its background thread.
+    pub fn build(self) -> io::Result<BlockingReporter> {
+        let (sender, receiver) = mpsc::sync_channel(self.queue_capacity);
+        let agent = ureq::AgentBuilder::new().timeout(self.timeout).build();
+
+        let worker = thread::Builder::new()
+            .name("zipkin-reporter".to_string())
+            .spawn(move || {
+                let mut spans = vec![];
+                let mut deadline = Instant::now() + self.flush_interval;
+
+                loop {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match receiver.recv_timeout(timeout) {
+                        Ok(Some(span)) => {
+                            spans.push(span);
+                            if spans.len() >= self.batch_size {
+                                post_spans(&agent, &self, &mut spans);
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            post_spans(&agent, &self, &mut spans);
+                            deadline = Instant::now() + self.flush_interval;
+                        }
+                        Ok(None) | Err(RecvTimeoutError::Disconnected) => {
+                            post_spans(&agent, &self, &mut spans);
+                            break;
+                        }
+                    }
+                }
+            })?;
+
+        Ok(BlockingReporter {
+            sender,
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+}
+
+#[cfg(feature = "blocking-reporter")]
+fn post_spans(agent: &ureq::Agent, config: &BlockingReporterBuilder, spans: &mut Vec<Span>) {
+    if spans.is_empty() {
+        return;
+    }
+
+    if config.dedupe {
+        let mut seen = HashSet::new();
+        spans.retain(|span| seen.insert((span.trace_id(), span.id())));
+    }
+
+    let mut buf = vec![];
+    crate::json::encode_spans_with(spans, config.timestamp_encoding, &mut buf);
+    spans.clear();
+
+    #[cfg(feature = "debug-hooks")]
+    if let Some(on_request) = &config.on_request {
+        on_request(&buf);
+    }
+
+    for (i, url) in config.urls.iter().enumerate() {
+        match post_body(agent, url, &buf) {
+            Ok(()) => return,
+            Err(e) if matches!(*e, ureq::Error::Transport(_)) && i + 1 < config.urls.len() => {
+                warn!(
+                    "error sending spans to {}, trying next collector: {}",
+                    url, e
+                );
+            }
+            Err(e) => {
+                warn!("error sending spans: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "blocking-reporter")]
+fn post_body(agent: &ureq::Agent, url: &str, buf: &[u8]) -> Result<(), Box<ureq::Error>> {
+    let mut retried = false;
+    loop {
+        let e = match agent
+            .post(url)
+            .set("Content-Type", "application/json")
+            .send_bytes(buf)
+        {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+
+        if let ureq::Error::Status(429 | 503, response) = &e {
+            let delay = response
+                .header("Retry-After")
+                .and_then(|v| parse_retry_after(v, SystemTime::now()));
+            if let (Some(delay), false) = (delay, retried) {
+                // sleeping in the worker also delays the requests for any spans reported in the meantime
+                thread::sleep(delay.min(MAX_RETRY_AFTER));
+                retried = true;
+                continue;
+            }
+        }
+
+        return Err(Box::new(e));
+    }
+}
+
+#[cfg(feature = "blocking-reporter")]
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Parses a Retry-After header value into the delay it requests, relative to `now`.
+//
+// Both the delay-seconds and HTTP-date forms are supported, but only the preferred IMF-fixdate format of the latter,
+// e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+#[cfg(feature = "blocking-reporter")]
+pub(crate) fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let mut parts = value.split(' ');
+    let _day_of_week = parts.next()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    let month = match parts.next()? {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let mut time = parts.next()?.split(':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if parts.next()? != "GMT" || parts.next().is_some() || time.next().is_some() {
+        return None;
+    }
+
+    // days since the Unix epoch of the civil date - see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    let date = UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?);
+    Some(date.duration_since(now).unwrap_or(Duration::from_secs(0)))
+}
@@ -13,8 +13,12 @@
 //  limitations under the License.
 
 //! Span samplers.
-use crate::TraceId;
-use std::sync::Arc;
+use crate::{TraceContext, TraceId};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// A sampler decides whether or not a span should be recorded based on its
 /// trace ID.
@@ -26,6 +30,30 @@ pub trait Sample {
     /// Returns `true` if the span associated with the trace ID should be
     /// recorded.
     fn sample(&self, trace_id: TraceId) -> bool;
+
+    /// Returns `true` if the span associated with the trace ID and name should
+    /// be recorded.
+    ///
+    /// The name is `None` if it was not known when the span was created.
+    ///
+    /// The default implementation ignores the name and delegates to `sample`.
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        let _ = name;
+        self.sample(trace_id)
+    }
+
+    /// Returns `true` if the span with the trace context and name should be
+    /// recorded.
+    ///
+    /// This is the method called by the tracer. The context's sampling
+    /// decision has not been made yet, but its other fields, like the parent
+    /// span ID, are available.
+    ///
+    /// The default implementation delegates to `sample_named` with the
+    /// context's trace ID.
+    fn sample_context(&self, context: &TraceContext, name: Option<&str>) -> bool {
+        self.sample_named(context.trace_id(), name)
+    }
 }
 
 impl<T> Sample for Arc<T>
@@ -35,6 +63,14 @@ where
     fn sample(&self, trace_id: TraceId) -> bool {
         (**self).sample(trace_id)
     }
+
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        (**self).sample_named(trace_id, name)
+    }
+
+    fn sample_context(&self, context: &TraceContext, name: Option<&str>) -> bool {
+        (**self).sample_context(context, name)
+    }
 }
 
 impl<T> Sample for Box<T>
@@ -44,6 +80,36 @@ where
     fn sample(&self, trace_id: TraceId) -> bool {
         (**self).sample(trace_id)
     }
+
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        (**self).sample_named(trace_id, name)
+    }
+
+    fn sample_context(&self, context: &TraceContext, name: Option<&str>) -> bool {
+        (**self).sample_context(context, name)
+    }
+}
+
+/// A policy which can override the sampling decision of a trace context received from a remote service.
+///
+/// The tracer consults its policy in `join_trace` and `rpc_server_span`, before the context's own decision or the
+/// tracer's sampler is used. This allows a decision made elsewhere, for example by a sidecar, to take precedence.
+/// Contexts in debug mode are always sampled, so the policy is not consulted for them and cannot override them.
+///
+/// It is implemented for closures taking a `&TraceContext` and returning an `Option<bool>`.
+pub trait SamplingPolicy {
+    /// Returns `Some(true)` or `Some(false)` to force the context to be sampled or unsampled, or `None` to leave its
+    /// decision unchanged.
+    fn decide(&self, context: &TraceContext) -> Option<bool>;
+}
+
+impl<F> SamplingPolicy for F
+where
+    F: Fn(&TraceContext) -> Option<bool>,
+{
+    fn decide(&self, context: &TraceContext) -> Option<bool> {
+        self(context)
+    }
 }
 
 /// A `Sample`r which always returns `true`.
@@ -67,6 +133,7 @@ impl Sample for NeverSampler {
 /// A `Sample`r which randomly samples at a specific rate.
 pub struct RandomSampler {
     rate: f32,
+    rng: Option<Mutex<StdRng>>,
 }
 
 impl RandomSampler {
@@ -77,12 +144,277 @@ impl RandomSampler {
     /// Panics if `rate` is less than 0 or greater than 1.
     pub fn new(rate: f32) -> RandomSampler {
         assert!((0. ..=1.).contains(&rate));
-        RandomSampler { rate }
+        RandomSampler { rate, rng: None }
+    }
+
+    /// Creates a new `RandomSampler` at the specified rate, using a random number generator with a fixed seed.
+    ///
+    /// Samplers created with the same seed make the same sequence of sampling decisions, which is useful in tests.
+    /// The generator is shared between threads, so this sampler is slower than one created by `new` under contention.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is less than 0 or greater than 1.
+    pub fn with_seed(rate: f32, seed: u64) -> RandomSampler {
+        assert!((0. ..=1.).contains(&rate));
+        RandomSampler {
+            rate,
+            rng: Some(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
     }
 }
 
 impl Sample for RandomSampler {
     fn sample(&self, _: TraceId) -> bool {
-        rand::random::<f32>() < self.rate
+        let value = match &self.rng {
+            Some(rng) => rng.lock().unwrap().gen::<f32>(),
+            None => rand::random::<f32>(),
+        };
+        value < self.rate
+    }
+}
+
+/// A `Sample`r which deterministically samples at a specific rate based on the trace ID.
+///
+/// The sampling decision is a function of the lower 64 bits of the trace ID, so all services using this sampler at
+/// the same rate will agree on which traces to sample.
+pub struct DeterministicSampler {
+    boundary: u64,
+}
+
+impl DeterministicSampler {
+    /// Creates a new `DeterministicSampler` at the specified rate.
+    ///
+    /// The rate has a precision of 0.0001.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is less than 0 or greater than 1.
+    pub fn new(rate: f32) -> DeterministicSampler {
+        assert!((0. ..=1.).contains(&rate));
+        DeterministicSampler {
+            boundary: (rate * 10_000.).round() as u64,
+        }
+    }
+}
+
+impl Sample for DeterministicSampler {
+    fn sample(&self, trace_id: TraceId) -> bool {
+        let bytes = trace_id.bytes();
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[bytes.len() - 8..]);
+        u64::from_be_bytes(buf) % 10_000 < self.boundary
+    }
+}
+
+/// A `Sample`r which wraps another, counting the number of sampling decisions it makes.
+pub struct StatsSampler<S> {
+    inner: S,
+    considered: AtomicU64,
+    sampled: AtomicU64,
+}
+
+impl<S> StatsSampler<S> {
+    /// Creates a new `StatsSampler` wrapping another sampler.
+    pub fn new(inner: S) -> StatsSampler<S> {
+        StatsSampler {
+            inner,
+            considered: AtomicU64::new(0),
+            sampled: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a shared reference to the inner sampler.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the number of trace IDs that have been passed to the sampler.
+    pub fn considered(&self) -> u64 {
+        self.considered.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of trace IDs that the sampler has decided to sample.
+    pub fn sampled(&self) -> u64 {
+        self.sampled.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, sampled: bool) -> bool {
+        self.considered.fetch_add(1, Ordering::Relaxed);
+        if sampled {
+            self.sampled.fetch_add(1, Ordering::Relaxed);
+        }
+        sampled
+    }
+}
+
+impl<S> Sample for StatsSampler<S>
+where
+    S: Sample,
+{
+    fn sample(&self, trace_id: TraceId) -> bool {
+        let sampled = self.inner.sample(trace_id);
+        self.record(sampled)
+    }
+
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        let sampled = self.inner.sample_named(trace_id, name);
+        self.record(sampled)
+    }
+
+    fn sample_context(&self, context: &TraceContext, name: Option<&str>) -> bool {
+        let sampled = self.inner.sample_context(context, name);
+        self.record(sampled)
+    }
+}
+
+/// A `Sample`r which randomly samples at a rate determined by the span's name.
+///
+/// Rules are checked in the order they were added, and the first matching rule's rate is used. A pattern matches a
+/// name exactly, unless it ends with `*`, in which case it matches any name starting with the rest of the pattern.
+/// Spans whose names match no rule, or whose names are not known, are sampled at the default rate.
+pub struct RuleSampler {
+    rules: Vec<(String, RandomSampler)>,
+    default: RandomSampler,
+}
+
+impl RuleSampler {
+    /// Creates a new `RuleSampler` with no rules and the specified default rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default_rate` is less than 0 or greater than 1.
+    pub fn new(default_rate: f32) -> RuleSampler {
+        RuleSampler {
+            rules: vec![],
+            default: RandomSampler::new(default_rate),
+        }
+    }
+
+    /// Adds a rule sampling spans with names matching the pattern at the specified rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is less than 0 or greater than 1.
+    pub fn rule(&mut self, pattern: &str, rate: f32) -> &mut RuleSampler {
+        self.rules
+            .push((pattern.to_string(), RandomSampler::new(rate)));
+        self
+    }
+
+    /// A builder-style version of `rule`.
+    pub fn with_rule(mut self, pattern: &str, rate: f32) -> RuleSampler {
+        self.rule(pattern, rate);
+        self
+    }
+
+    fn sampler(&self, name: &str) -> &RandomSampler {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            })
+            .map_or(&self.default, |(_, sampler)| sampler)
+    }
+}
+
+impl Sample for RuleSampler {
+    fn sample(&self, trace_id: TraceId) -> bool {
+        self.default.sample(trace_id)
+    }
+
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        match name {
+            Some(name) => self.sampler(name).sample(trace_id),
+            None => self.default.sample(trace_id),
+        }
+    }
+}
+
+/// A `Sample`r which allows an environment variable to override the sampling decisions of another.
+///
+/// The environment variable's value is parsed case-insensitively, ignoring surrounding whitespace:
+///
+/// * `always` - all traces are sampled.
+/// * `never` - no traces are sampled.
+///
+/// If the variable is unset or has any other value, the decision is delegated to the inner sampler.
+pub struct EnvSampler<S> {
+    inner: S,
+    var: String,
+    cached: Option<Option<bool>>,
+}
+
+impl<S> EnvSampler<S> {
+    /// Creates a new `EnvSampler` which reads the environment variable once, when it is created.
+    pub fn new(var: &str, inner: S) -> EnvSampler<S> {
+        EnvSampler {
+            inner,
+            var: var.to_string(),
+            cached: Some(read_override(var)),
+        }
+    }
+
+    /// Creates a new `EnvSampler` which reads the environment variable each time a sampling decision is made.
+    ///
+    /// This allows the override to be changed while the program is running, at the cost of a lookup of the variable
+    /// for every new trace.
+    pub fn new_dynamic(var: &str, inner: S) -> EnvSampler<S> {
+        EnvSampler {
+            inner,
+            var: var.to_string(),
+            cached: None,
+        }
+    }
+
+    /// Returns a shared reference to the inner sampler.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    fn forced(&self) -> Option<bool> {
+        match self.cached {
+            Some(cached) => cached,
+            None => read_override(&self.var),
+        }
+    }
+}
+
+fn read_override(var: &str) -> Option<bool> {
+    let value = env::var_os(var)?;
+    let value = value.to_str()?.trim();
+    if value.eq_ignore_ascii_case("always") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("never") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+impl<S> Sample for EnvSampler<S>
+where
+    S: Sample,
+{
+    fn sample(&self, trace_id: TraceId) -> bool {
+        match self.forced() {
+            Some(sampled) => sampled,
+            None => self.inner.sample(trace_id),
+        }
+    }
+
+    fn sample_named(&self, trace_id: TraceId, name: Option<&str>) -> bool {
+        match self.forced() {
+            Some(sampled) => sampled,
+            None => self.inner.sample_named(trace_id, name),
+        }
+    }
+
+    fn sample_context(&self, context: &TraceContext, name: Option<&str>) -> bool {
+        match self.forced() {
+            Some(sampled) => sampled,
+            None => self.inner.sample_context(context, name),
+        }
     }
 }
@@ -38,6 +38,16 @@ impl SamplingFlags {
         }
     }
 
+    /// Creates `SamplingFlags` directly from raw values.
+    ///
+    /// Unlike `Builder::build`, this does not force `sampled` to `Some(true)` when `debug` is set, so flags can be
+    /// constructed exactly as received from a peer. Note that passing the flags back through a builder (including
+    /// `TraceContext`'s) will reapply that coercion.
+    #[inline]
+    pub fn from_raw(sampled: Option<bool>, debug: bool) -> SamplingFlags {
+        SamplingFlags { sampled, debug }
+    }
+
     /// Determines if sampling has been requested for this context.
     ///
     /// A value of `None` indicates that the service working in the context is
@@ -93,6 +103,9 @@ impl Builder {
     }
 
     /// Constructs `SamplingFlags`.
+    ///
+    /// If the debug flag is set, the sampling request is forced to `Some(true)`. Use `SamplingFlags::from_raw` to
+    /// bypass this.
     #[inline]
     pub fn build(&self) -> SamplingFlags {
         SamplingFlags {
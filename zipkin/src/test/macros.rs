@@ -12,8 +12,9 @@
 //  See the License for the specific language governing permissions and
 //  limitations under the License.
 use crate as zipkin; // hack to get the macro codegen to work in the same crate
-use crate::{spanned, test};
+use crate::{spanned, test, SamplingFlags, TraceContext};
 use futures::executor;
+use std::fmt::Display;
 
 fn is_send<T>(_: T)
 where
@@ -195,3 +196,155 @@ fn async_method() {
     assert_eq!(spans[1].parent_id(), Some(spans[2].id()));
     assert_eq!(spans[2].parent_id(), None);
 }
+
+#[test]
+fn blocking_join() {
+    #[spanned(name = "foobar", join = context)]
+    fn foo(context: Option<TraceContext>) {
+        zipkin::next_span().with_name("fizzbuzz");
+    }
+
+    test::init();
+
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .sampled(true)
+        .build();
+    foo(Some(context));
+
+    let span = zipkin::next_span().with_name("root");
+    foo(None);
+    drop(span);
+
+    let spans = test::take();
+    assert_eq!(spans.len(), 5);
+    assert_eq!(spans[0].name(), Some("fizzbuzz"));
+    assert_eq!(spans[1].name(), Some("foobar"));
+    assert_eq!(spans[0].trace_id(), context.trace_id());
+    assert_eq!(spans[0].parent_id(), Some(context.span_id()));
+    assert_eq!(spans[1].trace_id(), context.trace_id());
+    assert_eq!(spans[1].id(), context.span_id());
+    assert!(spans[1].shared());
+    assert_eq!(spans[2].name(), Some("fizzbuzz"));
+    assert_eq!(spans[3].name(), Some("foobar"));
+    assert_eq!(spans[4].name(), Some("root"));
+    assert_eq!(spans[2].parent_id(), Some(spans[3].id()));
+    assert_eq!(spans[3].parent_id(), Some(spans[4].id()));
+    assert!(!spans[3].shared());
+}
+
+#[test]
+fn async_join() {
+    #[spanned(name = "foobar", join = context)]
+    async fn foo(context: Option<TraceContext>) {
+        zipkin::next_span().with_name("fizzbuzz");
+    }
+
+    test::init();
+
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .sampled(true)
+        .build();
+    executor::block_on(foo(Some(context)));
+
+    let spans = test::take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("fizzbuzz"));
+    assert_eq!(spans[1].name(), Some("foobar"));
+    assert_eq!(spans[0].parent_id(), Some(context.span_id()));
+    assert_eq!(spans[1].id(), context.span_id());
+    assert!(spans[1].shared());
+}
+
+#[test]
+fn blocking_unsampled_parent() {
+    #[spanned(name = "foobar")]
+    fn foo() -> bool {
+        zipkin::is_recording()
+    }
+
+    test::init();
+
+    let span = zipkin::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    for _ in 0..1000 {
+        assert!(!foo());
+    }
+    drop(span);
+
+    assert!(test::take().is_empty());
+}
+
+#[test]
+fn blocking_generics() {
+    #[spanned(name = "foobar")]
+    fn foo<'a, T>(t: T, a: &'a str, b: &'a str) -> &'a str
+    where
+        T: Display,
+    {
+        if t.to_string() == a {
+            a
+        } else {
+            b
+        }
+    }
+
+    test::init();
+
+    assert_eq!(foo(1, "1", "2"), "1");
+
+    let spans = test::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name(), Some("foobar"));
+}
+
+#[test]
+fn async_generics() {
+    #[spanned(name = "foobar")]
+    async fn foo<'a, T>(t: T, a: &'a str, b: &'a str) -> &'a str
+    where
+        T: Display,
+    {
+        if t.to_string() == a {
+            a
+        } else {
+            b
+        }
+    }
+
+    is_send(foo(1, "1", "2"));
+
+    test::init();
+
+    assert_eq!(executor::block_on(foo(1, "1", "2")), "1");
+
+    let spans = test::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name(), Some("foobar"));
+}
+
+#[test]
+fn trait_methods() {
+    trait Foo {
+        fn foo<T: Display>(&self, t: T) -> String;
+    }
+
+    struct Bar;
+
+    impl Foo for Bar {
+        #[spanned(name = "foo")]
+        fn foo<T: Display>(&self, t: T) -> String {
+            t.to_string()
+        }
+    }
+
+    test::init();
+
+    assert_eq!(Bar.foo(1), "1");
+
+    let spans = test::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name(), Some("foo"));
+}
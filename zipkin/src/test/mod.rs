@@ -12,16 +12,26 @@
 //  See the License for the specific language governing permissions and
 //  limitations under the License.
 use crate::sample::AlwaysSampler;
-use crate::{Endpoint, Report, Span};
+use crate::{
+    Clock, CurrentGuard, Endpoint, Kind, Report, SamplingFlags, Span, TagKey, TraceContext,
+    TracerBuilder,
+};
 use futures::executor;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "macros")]
 mod macros;
+mod report;
+mod sample;
+mod sampling_flags;
+mod trace_context;
 
 thread_local! {
-    static SPANS: RefCell<Vec<Span>> = RefCell::new(vec![]);
+    static SPANS: RefCell<Vec<Span>> = const { RefCell::new(vec![]) };
+    static MOCK_INSTANT: Cell<Option<Instant>> = const { Cell::new(None) };
 }
 
 struct TestReporter;
@@ -32,9 +42,20 @@ impl Report for TestReporter {
     }
 }
 
+struct TestClock;
+
+impl Clock for TestClock {
+    fn now_instant(&self) -> Instant {
+        MOCK_INSTANT.with(|i| i.get()).unwrap_or_else(Instant::now)
+    }
+}
+
 fn init() {
-    let _ = crate::set_tracer(AlwaysSampler, TestReporter, Endpoint::builder().build());
+    let _ = TracerBuilder::new(AlwaysSampler, TestReporter, Endpoint::builder().build())
+        .clock(TestClock)
+        .init();
     SPANS.with(|s| s.borrow_mut().clear());
+    MOCK_INSTANT.with(|i| i.set(None));
 }
 
 fn take() -> Vec<Span> {
@@ -109,3 +130,525 @@ fn bind() {
     assert_eq!(spans[2].id(), other_root_context.span_id());
     assert_eq!(spans[2].parent_id(), None);
 }
+
+#[test]
+fn owned_and_borrowed_strings() {
+    init();
+
+    let service_name = String::from("service");
+    let borrowed = Endpoint::builder().service_name("service").build();
+    let owned = Endpoint::builder().service_name(service_name).build();
+    assert_eq!(borrowed.service_name(), Some("service"));
+    assert_eq!(owned.service_name(), Some("service"));
+
+    crate::next_span()
+        .with_name("Borrowed")
+        .with_tag("key", "value");
+    crate::next_span()
+        .with_name(String::from("Owned"))
+        .with_tag(String::from("key"), String::from("value"));
+
+    let spans = take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("Borrowed"));
+    assert_eq!(spans[0].tags()["key"], "value");
+    assert_eq!(spans[1].name(), Some("Owned"));
+    assert_eq!(spans[1].tags()["key"], "value");
+}
+
+#[test]
+fn annotations() {
+    init();
+
+    let mut span = crate::next_span()
+        .with_annotation("foo")
+        .with_annotation("bar");
+    let annotations = span
+        .annotations()
+        .iter()
+        .map(|a| a.value())
+        .collect::<Vec<_>>();
+    assert_eq!(annotations, ["foo", "bar"]);
+
+    span.clear_annotations();
+    assert!(span.annotations().is_empty());
+    span.annotate("baz");
+    drop(span);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].annotations().len(), 1);
+    assert_eq!(spans[0].annotations()[0].value(), "baz");
+}
+
+#[test]
+fn tag_values() {
+    init();
+
+    let mut span = crate::next_span().with_tag_value("int", -15i32);
+    span.tag_value("bool", true);
+    drop(span);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags()["int"], "-15");
+    assert_eq!(spans[0].tags()["bool"], "true");
+}
+
+#[test]
+fn tag_bytes() {
+    init();
+
+    let fingerprint = b"\x00\x01binary\xff";
+    crate::next_span().with_tag_bytes("fingerprint", fingerprint);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(
+        spans[0].tag_bytes("fingerprint").as_deref(),
+        Some(&fingerprint[..])
+    );
+}
+
+#[test]
+fn is_recording() {
+    init();
+
+    assert!(!crate::is_recording());
+
+    let span = crate::new_trace();
+    assert!(span.is_recording());
+    assert!(crate::is_recording());
+
+    let unsampled = crate::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    assert!(!unsampled.is_recording());
+    assert!(!crate::is_recording());
+
+    drop(unsampled);
+    assert!(crate::is_recording());
+    drop(span);
+    assert!(!crate::is_recording());
+}
+
+#[test]
+fn rpc_spans() {
+    init();
+
+    let client = crate::rpc_client_span("get_user");
+    let context = client.context();
+    // the server half would usually run in another process
+    let server = crate::rpc_server_span(context, "get_user");
+    assert_eq!(server.context(), context);
+    drop(server);
+    drop(client);
+
+    let spans = take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].id(), context.span_id());
+    assert_eq!(spans[0].name(), Some("get_user"));
+    assert!(matches!(spans[0].kind(), Some(Kind::Server)));
+    assert!(spans[0].shared());
+    assert_eq!(spans[1].id(), context.span_id());
+    assert_eq!(spans[1].name(), Some("get_user"));
+    assert!(matches!(spans[1].kind(), Some(Kind::Client)));
+    assert!(!spans[1].shared());
+}
+
+#[test]
+fn open_span_debug() {
+    init();
+
+    let span = crate::next_span();
+    let debug = format!("{:?}", span);
+    assert!(debug.contains(&format!("{:?}", span.context().span_id())));
+    assert!(debug.contains("Attached"));
+    assert!(debug.contains("recording: true"));
+
+    let span = span.detach();
+    assert!(format!("{:?}", span).contains("Detached"));
+    drop(span);
+
+    let span = crate::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    assert!(format!("{:?}", span).contains("recording: false"));
+}
+
+#[test]
+fn next_span_named() {
+    init();
+
+    let parent = crate::next_span_named("Parent");
+    assert!(parent.is_recording());
+    let child = crate::next_span_named("child");
+    assert_eq!(
+        child.context().parent_id(),
+        Some(parent.context().span_id())
+    );
+    drop(child);
+    drop(parent);
+
+    let spans = take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("child"));
+    assert_eq!(spans[1].name(), Some("Parent"));
+    assert_eq!(spans[1].parent_id(), None);
+}
+
+#[test]
+fn finish() {
+    init();
+
+    let start = Instant::now();
+    MOCK_INSTANT.with(|i| i.set(Some(start)));
+    let span = crate::next_span().with_name("foo").with_tag("a", "b");
+    let context = span.context();
+    MOCK_INSTANT.with(|i| i.set(Some(start + Duration::from_millis(5))));
+
+    let span = span.finish().unwrap();
+    assert_eq!(span.trace_id(), context.trace_id());
+    assert_eq!(span.id(), context.span_id());
+    assert_eq!(span.name(), Some("foo"));
+    assert_eq!(span.tags()["a"], "b");
+    assert_eq!(span.duration(), Some(Duration::from_millis(5)));
+    assert_eq!(crate::current(), None);
+    assert!(take().is_empty());
+
+    let span = crate::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    assert!(span.finish().is_none());
+    assert!(take().is_empty());
+}
+
+#[test]
+fn tracer_installed() {
+    init();
+
+    assert!(crate::tracer_installed());
+}
+
+#[test]
+fn unsampled() {
+    init();
+
+    let span = crate::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    assert_eq!(span.context().sampled(), Some(false));
+    let child = crate::next_span().with_name("child").with_tag("foo", "bar");
+    assert!(!child.is_recording());
+    assert_eq!(child.context().sampled(), Some(false));
+    drop(child);
+    drop(span);
+
+    assert!(take().is_empty());
+}
+
+#[test]
+fn local_component() {
+    init();
+
+    crate::next_span().with_local_component("database");
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags()["lc"], "database");
+    assert_eq!(
+        spans[0].tags().keys().collect::<Vec<_>>(),
+        [TagKey::LOCAL_COMPONENT.as_str()]
+    );
+    assert!(TagKey::ALL.contains(&TagKey::LOCAL_COMPONENT));
+}
+
+#[test]
+fn local_endpoint_and_debug() {
+    init();
+
+    let endpoint = Endpoint::builder().service_name("other").build();
+    let span = crate::next_span()
+        .with_local_endpoint(endpoint.clone())
+        .with_debug(true);
+    assert!(!span.context().debug());
+    drop(span);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].local_endpoint(), Some(&endpoint));
+    assert!(spans[0].debug());
+
+    crate::next_span();
+
+    let spans = take();
+    assert_eq!(
+        spans[0].local_endpoint(),
+        Some(&Endpoint::builder().build())
+    );
+    assert!(!spans[0].debug());
+}
+
+#[test]
+fn tag_keys() {
+    init();
+
+    crate::next_span()
+        .with_tag(TagKey::HTTP_METHOD, "GET")
+        .with_tag_value(TagKey::HTTP_STATUS_CODE, 200)
+        .with_tag("http.path", "/foo");
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags()["http.method"], "GET");
+    assert_eq!(spans[0].tags()["http.status_code"], "200");
+    assert_eq!(spans[0].tags()[TagKey::HTTP_PATH.as_str()], "/foo");
+}
+
+#[test]
+fn new_child_detached() {
+    init();
+
+    let root = crate::new_trace();
+    let root_context = root.context();
+    let other = crate::new_trace().detach();
+
+    let child = crate::new_child_detached(other.context());
+    assert_eq!(crate::current(), Some(root_context));
+    assert_eq!(child.context().trace_id(), other.context().trace_id());
+    assert_eq!(child.context().parent_id(), Some(other.context().span_id()));
+    let child_id = child.context().span_id();
+
+    drop(child);
+    assert_eq!(crate::current(), Some(root_context));
+    drop(other);
+    drop(root);
+
+    let spans = take();
+    assert_eq!(spans.len(), 3);
+    assert_eq!(spans[0].id(), child_id);
+}
+
+#[test]
+fn child_context() {
+    init();
+
+    let span = crate::new_trace();
+    let contexts = (0..3).map(|_| span.child_context()).collect::<Vec<_>>();
+    assert_eq!(crate::current(), Some(span.context()));
+
+    for (i, context) in contexts.iter().enumerate() {
+        assert_eq!(context.trace_id(), span.context().trace_id());
+        assert_eq!(context.parent_id(), Some(span.context().span_id()));
+        assert_eq!(context.sampled(), span.context().sampled());
+        for other in &contexts[i + 1..] {
+            assert_ne!(context.span_id(), other.span_id());
+        }
+    }
+
+    drop(span);
+    assert_eq!(take().len(), 1);
+}
+
+#[test]
+fn clock() {
+    init();
+
+    let start = Instant::now();
+    MOCK_INSTANT.with(|i| i.set(Some(start)));
+    let span = crate::next_span();
+    MOCK_INSTANT.with(|i| i.set(Some(start + Duration::from_millis(1500))));
+    drop(span);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].duration(), Some(Duration::from_millis(1500)));
+}
+
+#[test]
+fn tag_key_sanitized() {
+    init();
+
+    crate::next_span().with_tag("foo\nbar", "baz");
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags()["foo_bar"], "baz");
+}
+
+#[test]
+fn instrument() {
+    init();
+
+    let span = crate::next_span();
+    let context = span.context();
+
+    let future = crate::instrument(async {
+        let before = crate::current();
+        futures::future::ready(()).await;
+        let child = crate::next_span();
+        (before, crate::current(), child.context())
+    });
+    drop(span);
+    assert_eq!(crate::current(), None);
+
+    let (before, after, child) = executor::block_on(future);
+    assert_eq!(before, Some(context));
+    assert_eq!(after, Some(child));
+    assert_eq!(child.parent_id(), Some(context.span_id()));
+    assert_eq!(crate::current(), None);
+
+    let future = crate::instrument(async { crate::current() });
+    let _guard = crate::set_current(context);
+    assert_eq!(executor::block_on(future), None);
+}
+
+#[test]
+fn tags() {
+    init();
+
+    let tags = (1..=3).map(|i| (format!("key{}", i), i.to_string()));
+    crate::next_span().with_tags(tags);
+
+    let mut span = crate::new_trace_from(SamplingFlags::builder().sampled(false).build());
+    span.tags(vec![("key".to_string(), "value".to_string())]);
+    drop(span);
+
+    let spans = take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags().len(), 3);
+    assert_eq!(spans[0].tags()["key1"], "1");
+    assert_eq!(spans[0].tags()["key2"], "2");
+    assert_eq!(spans[0].tags()["key3"], "3");
+}
+
+#[test]
+fn http_tags() {
+    init();
+
+    crate::next_span()
+        .with_http_request("GET", "/foo")
+        .with_http_response(200);
+    crate::next_span()
+        .with_http_request("POST", "/bar")
+        .with_http_response(503);
+
+    let spans = take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].tags()["http.method"], "GET");
+    assert_eq!(spans[0].tags()["http.path"], "/foo");
+    assert_eq!(spans[0].tags()["http.status_code"], "200");
+    assert!(!spans[0].tags().contains_key("error"));
+    assert_eq!(spans[1].tags()["http.method"], "POST");
+    assert_eq!(spans[1].tags()["http.status_code"], "503");
+    assert_eq!(spans[1].tags()["error"], "503");
+}
+
+#[test]
+fn spawn() {
+    init();
+
+    let span = crate::next_span();
+    let context = span.context();
+
+    let handle = crate::spawn(|| {
+        let before = crate::current();
+        let child = crate::next_span();
+        (before, child.context())
+    });
+    drop(span);
+
+    let (before, child) = handle.join().unwrap();
+    assert_eq!(before, Some(context));
+    assert_eq!(child.trace_id(), context.trace_id());
+    assert_eq!(child.parent_id(), Some(context.span_id()));
+}
+
+#[test]
+fn current_guards_out_of_order() {
+    let a = TraceContext::builder()
+        .trace_id([0; 8].into())
+        .span_id([1; 8].into())
+        .build();
+    let b = TraceContext::builder()
+        .trace_id([0; 8].into())
+        .span_id([2; 8].into())
+        .build();
+    let c = TraceContext::builder()
+        .trace_id([0; 8].into())
+        .span_id([3; 8].into())
+        .build();
+
+    let guard_a = crate::set_current(a);
+    let guard_b = crate::set_current(b);
+    let guard_c = crate::set_current(c);
+    assert_eq!(crate::current(), Some(c));
+
+    drop(guard_b);
+    assert_eq!(crate::current(), Some(c));
+
+    drop(guard_a);
+    assert_eq!(crate::current(), Some(c));
+
+    drop(guard_c);
+    assert_eq!(crate::current(), None);
+
+    let guard_a = crate::set_current(a);
+    let guard_b = crate::set_current(b);
+    drop(guard_b);
+    assert_eq!(crate::current(), Some(a));
+    drop(guard_a);
+    assert_eq!(crate::current(), None);
+}
+
+#[test]
+fn current_guard_in_thread_local_destructor() {
+    struct Holder(Option<CurrentGuard>);
+
+    impl Drop for Holder {
+        fn drop(&mut self) {
+            self.0.take();
+            let _guard = crate::set_current(
+                TraceContext::builder()
+                    .trace_id([0; 8].into())
+                    .span_id([2; 8].into())
+                    .build(),
+            );
+            crate::current();
+        }
+    }
+
+    thread_local! {
+        static HOLDER: RefCell<Holder> = const { RefCell::new(Holder(None)) };
+    }
+
+    thread::spawn(|| {
+        // thread local destructors run in reverse order of registration, so CURRENT is destroyed before HOLDER
+        HOLDER.with(|_| {});
+        let guard = crate::set_current(
+            TraceContext::builder()
+                .trace_id([0; 8].into())
+                .span_id([1; 8].into())
+                .build(),
+        );
+        HOLDER.with(|h| h.borrow_mut().0 = Some(guard));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn clear_current() {
+    init();
+
+    let stale = crate::next_span();
+    let stale_context = stale.context();
+
+    let root = crate::new_trace();
+    assert_eq!(root.context().parent_id(), None);
+    assert_ne!(root.context().trace_id(), stale_context.trace_id());
+    drop(root);
+
+    {
+        let _guard = crate::clear_current();
+        assert_eq!(crate::current(), None);
+
+        let span = crate::next_span();
+        assert_eq!(span.context().parent_id(), None);
+        assert_ne!(span.context().trace_id(), stale_context.trace_id());
+    }
+
+    assert_eq!(crate::current(), Some(stale_context));
+}
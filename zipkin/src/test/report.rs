@@ -0,0 +1,701 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use crate::report::{ManualReporter, MapReporter, TailSampleReporter};
+use crate::{span, Report, Span, SpanId, TraceId};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+fn span(id: u8) -> Span {
+    Span::builder()
+        .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+        .id(SpanId::from([id; 8]))
+        .timestamp(UNIX_EPOCH + Duration::from_secs(1_500_000_000))
+        .duration(Duration::from_millis(1))
+        .build()
+}
+
+#[derive(Default)]
+struct SingleReporter {
+    spans: Mutex<Vec<Span>>,
+}
+
+impl Report for SingleReporter {
+    fn report(&self, span: Span) {
+        self.spans.lock().unwrap().push(span);
+    }
+}
+
+#[derive(Default)]
+struct BatchReporter {
+    batches: Mutex<Vec<Vec<Span>>>,
+}
+
+impl Report for BatchReporter {
+    fn report(&self, span: Span) {
+        self.report_batch(vec![span]);
+    }
+
+    fn report_batch(&self, spans: Vec<Span>) {
+        self.batches.lock().unwrap().push(spans);
+    }
+}
+
+#[derive(Default)]
+struct BufferingReporter {
+    buf: Mutex<Vec<Span>>,
+    inner: SingleReporter,
+}
+
+impl Report for BufferingReporter {
+    fn report(&self, span: Span) {
+        self.buf.lock().unwrap().push(span);
+    }
+
+    fn shutdown(&self) {
+        let spans = mem::take(&mut *self.buf.lock().unwrap());
+        self.inner.report_batch(spans);
+    }
+}
+
+#[test]
+fn report_arc() {
+    #[derive(Default)]
+    struct ArcReporter {
+        spans: Mutex<Vec<Arc<Span>>>,
+    }
+
+    impl Report for ArcReporter {
+        fn report(&self, span: Span) {
+            self.report_arc(Arc::new(span));
+        }
+
+        fn report_arc(&self, span: Arc<Span>) {
+            self.spans.lock().unwrap().push(span);
+        }
+    }
+
+    let a = ArcReporter::default();
+    let b = ArcReporter::default();
+    let c = Box::new(SingleReporter::default());
+
+    let span = Arc::new(span(1));
+    a.report_arc(span.clone());
+    b.report_arc(span.clone());
+    c.report_arc(span.clone());
+
+    assert!(Arc::ptr_eq(&a.spans.lock().unwrap()[0], &span));
+    assert!(Arc::ptr_eq(&b.spans.lock().unwrap()[0], &span));
+    assert_eq!(c.spans.lock().unwrap()[0].id(), span.id());
+}
+
+#[test]
+fn map_reporter_redact() {
+    let reporter = MapReporter::new(SingleReporter::default(), |span: Span| {
+        let mut builder = span::Builder::from(span);
+        builder.tag("password", "<redacted>");
+        Some(builder.build())
+    });
+
+    let mut span = span::Builder::from(span(1));
+    span.tag("password", "hunter2").tag("user", "admin");
+    reporter.report(span.build());
+
+    let spans = reporter.get_ref().spans.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].tags()["password"], "<redacted>");
+    assert_eq!(spans[0].tags()["user"], "admin");
+}
+
+#[test]
+fn map_reporter_drop() {
+    let reporter = MapReporter::new(BatchReporter::default(), |span: Span| {
+        if span.id() == SpanId::from([2; 8]) {
+            None
+        } else {
+            Some(span)
+        }
+    });
+
+    reporter.report(span(2));
+    reporter.report_batch(vec![span(1), span(2), span(3)]);
+    reporter.report_batch(vec![span(2)]);
+
+    let batches = reporter.get_ref().batches.lock().unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].len(), 2);
+    assert_eq!(batches[0][0].id(), SpanId::from([1; 8]));
+    assert_eq!(batches[0][1].id(), SpanId::from([3; 8]));
+}
+
+#[test]
+fn manual_reporter() {
+    let reporter = ManualReporter::new(BatchReporter::default());
+
+    reporter.report(span(1));
+    reporter.report_batch(vec![span(2), span(3)]);
+    assert!(reporter.get_ref().batches.lock().unwrap().is_empty());
+
+    reporter.flush();
+    reporter.flush();
+
+    let batches = reporter.get_ref().batches.lock().unwrap();
+    assert_eq!(batches.len(), 1);
+    let ids = batches[0].iter().map(Span::id).collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [
+            SpanId::from([1; 8]),
+            SpanId::from([2; 8]),
+            SpanId::from([3; 8])
+        ]
+    );
+}
+
+#[test]
+fn tail_sample_reporter() {
+    let reporter = TailSampleReporter::new(BatchReporter::default(), |span: &Span| {
+        span.tags().contains_key("error")
+    });
+
+    let error = |id| {
+        let mut span = span::Builder::from(span(id));
+        span.tag("error", "500");
+        span.build()
+    };
+
+    reporter.report(span(1));
+    reporter.report(error(2));
+    reporter.report_batch(vec![span(3), error(4), span(5)]);
+    reporter.report_batch(vec![span(6)]);
+
+    let batches = reporter.get_ref().batches.lock().unwrap();
+    let ids = batches
+        .iter()
+        .map(|b| b.iter().map(|s| s.id()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [vec![SpanId::from([2; 8])], vec![SpanId::from([4; 8])]]
+    );
+}
+
+#[test]
+fn default_report_batch() {
+    let reporter = SingleReporter::default();
+    reporter.report_batch(vec![span(1), span(2), span(3)]);
+
+    let spans = reporter.spans.into_inner().unwrap();
+    assert_eq!(spans.len(), 3);
+    assert_eq!(spans[0].id(), SpanId::from([1; 8]));
+    assert_eq!(spans[1].id(), SpanId::from([2; 8]));
+    assert_eq!(spans[2].id(), SpanId::from([3; 8]));
+}
+
+#[test]
+fn custom_report_batch() {
+    let reporter = Box::new(BatchReporter::default());
+    reporter.report_batch(vec![span(1), span(2)]);
+
+    let batches = reporter.batches.into_inner().unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].len(), 2);
+    assert_eq!(batches[0][0].id(), SpanId::from([1; 8]));
+    assert_eq!(batches[0][1].id(), SpanId::from([2; 8]));
+}
+
+#[test]
+fn shutdown_flushes() {
+    let reporter = Arc::new(BufferingReporter::default());
+    let handle: Arc<dyn Report> = reporter.clone();
+    handle.report(span(1));
+    handle.report(span(2));
+    assert!(reporter.inner.spans.lock().unwrap().is_empty());
+
+    handle.shutdown();
+    assert!(reporter.buf.lock().unwrap().is_empty());
+    let spans = reporter.inner.spans.lock().unwrap();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].id(), SpanId::from([1; 8]));
+    assert_eq!(spans[1].id(), SpanId::from([2; 8]));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn udp_reporter() {
+    use crate::report::UdpReporter;
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let reporter = UdpReporter::new(socket.local_addr().unwrap()).unwrap();
+
+    let mut buf = [0; 1024];
+
+    reporter.report(span(1));
+    let len = socket.recv(&mut buf).unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1)], &mut expected);
+    assert_eq!(&buf[..len], &*expected);
+
+    reporter.report_batch(vec![span(2), span(3)]);
+    let len = socket.recv(&mut buf).unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(2), span(3)], &mut expected);
+    assert_eq!(&buf[..len], &*expected);
+
+    #[cfg(feature = "serde")]
+    {
+        let spans = serde_json::from_slice::<Vec<Span>>(&buf[..len]).unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].id(), SpanId::from([2; 8]));
+        assert_eq!(spans[1].id(), SpanId::from([3; 8]));
+    }
+}
+
+#[cfg(feature = "blocking-reporter")]
+fn read_request(stream: &mut std::net::TcpStream) -> (String, Vec<u8>) {
+    read_request_with_response(
+        stream,
+        b"HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    )
+}
+
+#[cfg(feature = "blocking-reporter")]
+fn read_request_with_response(
+    stream: &mut std::net::TcpStream,
+    response: &[u8],
+) -> (String, Vec<u8>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let mut reader = BufReader::new(&mut *stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse().unwrap();
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    stream.write_all(response).unwrap();
+
+    (request_line.trim_end().to_string(), body)
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api/v2/spans", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream)
+    });
+
+    let reporter = BlockingReporter::builder(&url)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.report(span(2));
+    reporter.shutdown();
+
+    let (request_line, body) = server.join().unwrap();
+    assert_eq!(request_line, "POST /api/v2/spans HTTP/1.1");
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1), span(2)], &mut expected);
+    assert_eq!(body, expected);
+
+    // spans reported after shutdown are dropped
+    reporter.report(span(3));
+    reporter.shutdown();
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_drop() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = tx.send(read_request(&mut stream).1);
+    });
+
+    let reporter = BlockingReporter::builder(&url)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    drop(reporter);
+
+    // the worker sends the queued span as soon as the reporter is dropped rather than waiting for the flush interval
+    let body = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1)], &mut expected);
+    assert_eq!(body, expected);
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_batch_size() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream).1
+    });
+
+    let reporter = BlockingReporter::builder(&url)
+        .batch_size(2)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.report(span(2));
+
+    let body = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1), span(2)], &mut expected);
+    assert_eq!(body, expected);
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_failover() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    // nothing is listening on the primary's port once its listener is dropped
+    let primary = TcpListener::bind("127.0.0.1:0").unwrap();
+    let primary_url = format!("http://{}/", primary.local_addr().unwrap());
+    drop(primary);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let secondary_url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream).1
+    });
+
+    let reporter = BlockingReporter::builder(&primary_url)
+        .add_url(&secondary_url)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.shutdown();
+
+    let body = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1)], &mut expected);
+    assert_eq!(body, expected);
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_failover_unresolvable_host() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let secondary_url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream).1
+    });
+
+    // the .invalid TLD is reserved and never resolves
+    let reporter = BlockingReporter::builder("http://zipkin.invalid/")
+        .add_url(&secondary_url)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.shutdown();
+
+    let body = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1)], &mut expected);
+    assert_eq!(body, expected);
+}
+
+#[test]
+#[cfg(feature = "debug-hooks")]
+fn blocking_reporter_on_request() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream).1
+    });
+
+    let requests = Arc::new(Mutex::new(vec![]));
+    let reporter = BlockingReporter::builder(&url)
+        .flush_interval(Duration::from_secs(60))
+        .on_request({
+            let requests = requests.clone();
+            move |body| requests.lock().unwrap().push(body.to_vec())
+        })
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.report(span(2));
+    reporter.shutdown();
+
+    let body = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1), span(2)], &mut expected);
+    assert_eq!(body, expected);
+    assert_eq!(*requests.lock().unwrap(), [expected]);
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_dedupe() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream).1
+    });
+
+    let reporter = BlockingReporter::builder(&url)
+        .dedupe(true)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.report(span(2));
+    reporter.report(span(1));
+    reporter.shutdown();
+
+    let body = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1), span(2)], &mut expected);
+    assert_eq!(body, expected);
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct CapturingSubscriber {
+    events: Mutex<Vec<Vec<(String, String)>>>,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for CapturingSubscriber {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        struct Visitor(Vec<(String, String)>);
+
+        impl tracing::field::Visit for Visitor {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .push((field.name().to_string(), format!("{:?}", value)));
+            }
+        }
+
+        assert_eq!(event.metadata().target(), "zipkin");
+        let mut visitor = Visitor(vec![]);
+        event.record(&mut visitor);
+        self.events.lock().unwrap().push(visitor.0);
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn tracing_reporter() {
+    use crate::report::TracingReporter;
+
+    let subscriber = Arc::new(CapturingSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || {
+        let span = Span::builder()
+            .trace_id(TraceId::from([0, 1, 2, 3, 4, 5, 6, 7]))
+            .id(SpanId::from([1; 8]))
+            .name("foo")
+            .duration(Duration::from_millis(1))
+            .tag("b", "2")
+            .tag("a", "1")
+            .build();
+        TracingReporter.report(span);
+    });
+
+    let events = subscriber.events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let fields = events[0]
+        .iter()
+        .map(|(k, v)| (&**k, &**v))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("message", "span finished"),
+            ("trace_id", "0001020304050607"),
+            ("span_id", "0101010101010101"),
+            ("name", "\"foo\""),
+            ("duration_us", "1000"),
+            ("tags", r#"[("a", "1"), ("b", "2")]"#),
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn blocking_reporter_retry_after() {
+    use crate::report::BlockingReporter;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Instant;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let (_, first) = read_request_with_response(
+            &mut stream,
+            b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+        let rejected = Instant::now();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let (_, second) = read_request(&mut stream);
+        (first, second, rejected.elapsed())
+    });
+
+    let reporter = BlockingReporter::builder(&url)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    reporter.report(span(1));
+    reporter.shutdown();
+
+    let (first, second, elapsed) = server.join().unwrap();
+    let mut expected = vec![];
+    crate::json::encode_spans(&[span(1)], &mut expected);
+    assert_eq!(first, expected);
+    assert_eq!(second, expected);
+    assert!(elapsed >= Duration::from_millis(900), "{:?}", elapsed);
+}
+
+#[test]
+#[cfg(feature = "blocking-reporter")]
+fn parse_retry_after() {
+    use crate::report::parse_retry_after;
+
+    let now = UNIX_EPOCH + Duration::from_secs(784_111_777); // Sun, 06 Nov 1994 08:49:37 GMT
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:51:37 GMT", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT", now),
+        Some(Duration::from_secs(0))
+    );
+    assert_eq!(
+        parse_retry_after("Sat, 01 Jan 2000 00:00:00 GMT", UNIX_EPOCH),
+        Some(Duration::from_secs(946_684_800))
+    );
+    assert_eq!(
+        parse_retry_after("Thu, 29 Feb 2024 12:00:00 GMT", UNIX_EPOCH),
+        Some(Duration::from_secs(1_709_208_000))
+    );
+    assert_eq!(
+        parse_retry_after("Sunday, 06-Nov-94 08:49:37 GMT", now),
+        None
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+    assert_eq!(parse_retry_after("-1", now), None);
+}
+
+fn assert_send_sync<T>()
+where
+    T: Send + Sync,
+{
+}
+
+#[test]
+fn reporters_send_sync() {
+    assert_send_sync::<crate::report::NopReporter>();
+    assert_send_sync::<crate::report::LoggingReporter>();
+    assert_send_sync::<MapReporter<SingleReporter, fn(Span) -> Option<Span>>>();
+    assert_send_sync::<TailSampleReporter<SingleReporter, fn(&Span) -> bool>>();
+    assert_send_sync::<ManualReporter<SingleReporter>>();
+    #[cfg(feature = "json")]
+    assert_send_sync::<crate::report::UdpReporter>();
+    #[cfg(feature = "blocking-reporter")]
+    assert_send_sync::<crate::report::BlockingReporter>();
+    #[cfg(feature = "tracing")]
+    assert_send_sync::<crate::report::TracingReporter>();
+}
@@ -0,0 +1,186 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use crate::sample::{
+    AlwaysSampler, DeterministicSampler, EnvSampler, NeverSampler, RandomSampler, RuleSampler,
+    StatsSampler,
+};
+use crate::{Sample, SpanId, TraceContext, TraceId};
+use std::env;
+
+#[test]
+fn stats_sampler() {
+    let sampler = StatsSampler::new(RandomSampler::new(0.5));
+    assert_eq!(sampler.considered(), 0);
+    assert_eq!(sampler.sampled(), 0);
+
+    let mut sampled = 0;
+    for i in 0..100 {
+        if sampler.sample(TraceId::from([i; 8])) {
+            sampled += 1;
+        }
+    }
+
+    assert_eq!(sampler.considered(), 100);
+    assert_eq!(sampler.sampled(), sampled);
+}
+
+#[test]
+fn rule_sampler() {
+    let sampler = RuleSampler::new(1.)
+        .with_rule("get /health", 0.)
+        .with_rule("internal *", 0.)
+        .with_rule("internal important", 1.);
+
+    for i in 0..100 {
+        let trace_id = TraceId::from([i; 8]);
+        assert!(sampler.sample(trace_id));
+        assert!(sampler.sample_named(trace_id, None));
+        assert!(sampler.sample_named(trace_id, Some("get /users")));
+        assert!(!sampler.sample_named(trace_id, Some("get /health")));
+        assert!(!sampler.sample_named(trace_id, Some("internal foo")));
+        // the earlier wildcard rule takes precedence
+        assert!(!sampler.sample_named(trace_id, Some("internal important")));
+    }
+}
+
+#[test]
+fn deterministic_sampler_consistent() {
+    let sampler = DeterministicSampler::new(0.5);
+
+    for _ in 0..100 {
+        let trace_id = TraceId::from(rand::random::<[u8; 16]>());
+        let sampled = sampler.sample(trace_id);
+        for _ in 0..10 {
+            assert_eq!(sampler.sample(trace_id), sampled);
+        }
+        assert_eq!(DeterministicSampler::new(0.5).sample(trace_id), sampled);
+    }
+}
+
+#[test]
+fn deterministic_sampler_rate() {
+    let sampler = DeterministicSampler::new(0.25);
+
+    let sampled = (0..10_000)
+        .filter(|_| sampler.sample(TraceId::from(rand::random::<[u8; 8]>())))
+        .count();
+    assert!((2_000..3_000).contains(&sampled), "{}", sampled);
+
+    assert!(!DeterministicSampler::new(0.).sample(TraceId::from([0xff; 8])));
+    assert!(DeterministicSampler::new(1.).sample(TraceId::from([0xff; 8])));
+}
+
+#[test]
+fn env_sampler() {
+    let var = "ZIPKIN_TEST_ENV_SAMPLER";
+    let trace_id = TraceId::from([0; 8]);
+
+    env::remove_var(var);
+    assert!(!EnvSampler::new(var, NeverSampler).sample(trace_id));
+    assert!(EnvSampler::new(var, AlwaysSampler).sample(trace_id));
+
+    env::set_var(var, "always");
+    assert!(EnvSampler::new(var, NeverSampler).sample(trace_id));
+    assert!(EnvSampler::new(var, NeverSampler).sample_named(trace_id, Some("foo")));
+
+    env::set_var(var, " NEVER ");
+    assert!(!EnvSampler::new(var, AlwaysSampler).sample(trace_id));
+    assert!(!EnvSampler::new(var, AlwaysSampler).sample_named(trace_id, None));
+
+    env::set_var(var, "sometimes");
+    assert!(!EnvSampler::new(var, NeverSampler).sample(trace_id));
+    assert!(EnvSampler::new(var, AlwaysSampler).sample(trace_id));
+
+    env::remove_var(var);
+}
+
+#[test]
+fn env_sampler_cached_and_dynamic() {
+    let var = "ZIPKIN_TEST_ENV_SAMPLER_DYNAMIC";
+    let trace_id = TraceId::from([0; 8]);
+
+    env::set_var(var, "always");
+    let cached = EnvSampler::new(var, NeverSampler);
+    let dynamic = EnvSampler::new_dynamic(var, NeverSampler);
+    assert!(cached.sample(trace_id));
+    assert!(dynamic.sample(trace_id));
+
+    env::remove_var(var);
+    assert!(cached.sample(trace_id));
+    assert!(!dynamic.sample(trace_id));
+}
+
+#[test]
+fn seeded_random_sampler() {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    let trace_id = TraceId::from([0; 8]);
+    let a = RandomSampler::with_seed(0.5, 42);
+    let b = RandomSampler::with_seed(0.5, 42);
+    let mut rng = StdRng::seed_from_u64(42);
+
+    let decisions = (0..100).map(|_| a.sample(trace_id)).collect::<Vec<_>>();
+    assert_eq!(
+        decisions,
+        (0..100).map(|_| b.sample(trace_id)).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        decisions,
+        (0..100).map(|_| rng.gen::<f32>() < 0.5).collect::<Vec<_>>()
+    );
+    assert!(decisions.contains(&true));
+    assert!(decisions.contains(&false));
+
+    let never = RandomSampler::with_seed(0., 42);
+    assert!((0..100).all(|_| !never.sample(trace_id)));
+    let always = RandomSampler::with_seed(1., 42);
+    assert!((0..100).all(|_| always.sample(trace_id)));
+}
+
+#[test]
+fn sample_context() {
+    // samples only root spans
+    struct RootSampler;
+
+    impl Sample for RootSampler {
+        fn sample(&self, _: TraceId) -> bool {
+            true
+        }
+
+        fn sample_context(&self, context: &TraceContext, _: Option<&str>) -> bool {
+            context.parent_id().is_none()
+        }
+    }
+
+    let root = TraceContext::builder()
+        .trace_id(TraceId::from([0; 8]))
+        .span_id(SpanId::from([1; 8]))
+        .build();
+    let child = TraceContext::builder()
+        .trace_id(TraceId::from([0; 8]))
+        .parent_id(SpanId::from([1; 8]))
+        .span_id(SpanId::from([2; 8]))
+        .build();
+
+    let sampler = StatsSampler::new(RootSampler);
+    assert!(sampler.sample_context(&root, None));
+    assert!(!sampler.sample_context(&child, None));
+    assert_eq!(sampler.considered(), 2);
+
+    // the default implementation delegates to sample_named
+    let sampler = RuleSampler::new(0.).with_rule("child", 1.);
+    assert!(!sampler.sample_context(&root, Some("root")));
+    assert!(sampler.sample_context(&child, Some("child")));
+}
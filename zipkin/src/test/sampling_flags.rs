@@ -0,0 +1,44 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use crate::SamplingFlags;
+
+#[test]
+fn from_raw() {
+    let flags = SamplingFlags::from_raw(Some(false), true);
+    assert_eq!(flags.sampled(), Some(false));
+    assert!(flags.debug());
+
+    let flags = SamplingFlags::from_raw(None, true);
+    assert_eq!(flags.sampled(), None);
+    assert!(flags.debug());
+
+    let flags = SamplingFlags::from_raw(Some(true), false);
+    assert_eq!(flags, SamplingFlags::builder().sampled(true).build());
+
+    assert_eq!(
+        SamplingFlags::from_raw(None, false),
+        SamplingFlags::default()
+    );
+}
+
+#[test]
+fn build_coerces_debug() {
+    let flags = SamplingFlags::builder().sampled(false).debug(true).build();
+    assert_eq!(flags.sampled(), Some(true));
+    assert!(flags.debug());
+
+    let raw = SamplingFlags::from_raw(Some(false), true);
+    let rebuilt = crate::sampling_flags::Builder::from(raw).build();
+    assert_eq!(rebuilt, SamplingFlags::from_raw(Some(true), true));
+}
@@ -0,0 +1,183 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use crate::TraceContext;
+
+fn round_trip(context: TraceContext, expected: &str) {
+    assert_eq!(context.to_string(), expected);
+    assert_eq!(expected.parse::<TraceContext>().unwrap(), context);
+}
+
+#[test]
+fn display_from_str_minimal() {
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .build();
+    round_trip(context, "0001020304050607-0203040506070809");
+}
+
+#[test]
+fn display_from_str_sampled() {
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .sampled(true)
+        .build();
+    round_trip(context, "0001020304050607-0203040506070809-1");
+}
+
+#[test]
+fn display_from_str_unsampled() {
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .sampled(false)
+        .build();
+    round_trip(context, "0001020304050607-0203040506070809-0");
+}
+
+#[test]
+fn display_from_str_debug() {
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .debug(true)
+        .build();
+    round_trip(context, "0001020304050607-0203040506070809-d");
+}
+
+#[test]
+fn display_from_str_parent() {
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15].into())
+        .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .sampled(true)
+        .build();
+    round_trip(
+        context,
+        "000102030405060708090a0b0c0d0e0f-0203040506070809-1-0102030405060708",
+    );
+}
+
+#[test]
+fn from_str_invalid() {
+    assert!("".parse::<TraceContext>().is_err());
+    assert!("0001020304050607".parse::<TraceContext>().is_err());
+    assert!("0001020304050607-02030405".parse::<TraceContext>().is_err());
+    assert!("0001020304050607-0203040506070809-x"
+        .parse::<TraceContext>()
+        .is_err());
+    assert!("0001020304050607-0203040506070809-1-0102030405060708-1"
+        .parse::<TraceContext>()
+        .is_err());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_ids_round_trip() {
+    use crate::{SpanId, TraceId};
+
+    let trace_id = TraceId::from([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    let json = serde_json::to_string(&trace_id).unwrap();
+    assert_eq!(json, r#""000102030405060708090a0b0c0d0e0f""#);
+    assert_eq!(serde_json::from_str::<TraceId>(&json).unwrap(), trace_id);
+
+    let span_id = SpanId::from([0, 1, 2, 3, 4, 5, 6, 7]);
+    let json = serde_json::to_string(&span_id).unwrap();
+    assert_eq!(json, r#""0001020304050607""#);
+    assert_eq!(serde_json::from_str::<SpanId>(&json).unwrap(), span_id);
+}
+
+#[test]
+fn span_builder_from_context() {
+    use crate::span;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .parent_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .debug(true)
+        .build();
+
+    let span = span::Builder::from(context)
+        .timestamp(UNIX_EPOCH + Duration::from_secs(1_500_000_000))
+        .duration(Duration::from_millis(5))
+        .build();
+    assert_eq!(span.trace_id(), context.trace_id());
+    assert_eq!(span.id(), context.span_id());
+    assert_eq!(span.parent_id(), context.parent_id());
+    assert!(span.debug());
+    assert_eq!(span.duration(), Some(Duration::from_millis(5)));
+
+    let context = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .build();
+    let span = span::Builder::from(context).build();
+    assert_eq!(span.parent_id(), None);
+    assert!(!span.debug());
+}
+
+#[test]
+fn bytes_round_trip() {
+    let short = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .build();
+    let bytes = short.to_bytes();
+    assert_eq!(bytes.len(), 17);
+    assert_eq!(TraceContext::from_bytes(&bytes), Some(short));
+
+    let long = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15].into())
+        .parent_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .sampled(false)
+        .build();
+    let bytes = long.to_bytes();
+    assert_eq!(bytes.len(), 33);
+    assert_eq!(TraceContext::from_bytes(&bytes), Some(long));
+
+    let short_with_parent = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7].into())
+        .parent_id([2, 3, 4, 5, 6, 7, 8, 9].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .debug(true)
+        .build();
+    let bytes = short_with_parent.to_bytes();
+    assert_eq!(bytes.len(), 25);
+    assert_eq!(TraceContext::from_bytes(&bytes), Some(short_with_parent));
+
+    let long_without_parent = TraceContext::builder()
+        .trace_id([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15].into())
+        .span_id([1, 2, 3, 4, 5, 6, 7, 8].into())
+        .sampled(true)
+        .build();
+    let bytes = long_without_parent.to_bytes();
+    assert_eq!(bytes.len(), 25);
+    assert_eq!(TraceContext::from_bytes(&bytes), Some(long_without_parent));
+}
+
+#[test]
+fn from_bytes_invalid() {
+    assert_eq!(TraceContext::from_bytes(&[]), None);
+    // truncated span ID
+    assert_eq!(TraceContext::from_bytes(&[0; 16]), None);
+    // parent ID flag without a parent ID
+    assert_eq!(TraceContext::from_bytes(&[0b10; 17]), None);
+    // unknown flag
+    assert_eq!(TraceContext::from_bytes(&[0x80; 17]), None);
+}
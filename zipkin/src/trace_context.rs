@@ -14,7 +14,11 @@
 
 //! Trace contexts.
 use crate::sampling_flags;
-use crate::{SamplingFlags, SpanId, TraceId};
+use crate::{span, SamplingFlags, Span, SpanId, TraceId};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
 /// A `TraceContext` represents a distributed trace request.
 ///
@@ -84,8 +88,190 @@ impl TraceContext {
     pub fn debug(&self) -> bool {
         self.flags.debug()
     }
+
+    /// Encodes the context in a compact binary format.
+    ///
+    /// The encoding consists of a flags byte followed by the trace ID (8 or 16 bytes), the span ID (8 bytes), and the
+    /// parent ID (8 bytes, if present). The bits of the flags byte, starting from the least significant, are:
+    ///
+    /// * 0 - set if the trace ID is 16 bytes rather than 8.
+    /// * 1 - set if the parent ID is present.
+    /// * 2 - set if a sampling decision has been made.
+    /// * 3 - set if the context is sampled.
+    /// * 4 - set if the context is in debug mode.
+    ///
+    /// The encoding is at most 33 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.trace_id.bytes().len() == 16 {
+            flags |= LONG_TRACE_ID;
+        }
+        if self.parent_id.is_some() {
+            flags |= PARENT_ID;
+        }
+        if let Some(sampled) = self.sampled() {
+            flags |= SAMPLING_DECIDED;
+            if sampled {
+                flags |= SAMPLED;
+            }
+        }
+        if self.debug() {
+            flags |= DEBUG;
+        }
+
+        let mut buf = Vec::with_capacity(33);
+        buf.push(flags);
+        buf.extend_from_slice(self.trace_id.bytes());
+        buf.extend_from_slice(self.span_id.bytes());
+        if let Some(parent_id) = self.parent_id {
+            buf.extend_from_slice(parent_id.bytes());
+        }
+        buf
+    }
+
+    /// Decodes a context from the binary format produced by `to_bytes`.
+    ///
+    /// Returns `None` if the bytes are not a valid encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Option<TraceContext> {
+        let (&flags, rest) = bytes.split_first()?;
+        if flags & !(LONG_TRACE_ID | PARENT_ID | SAMPLING_DECIDED | SAMPLED | DEBUG) != 0 {
+            return None;
+        }
+
+        let trace_id_len = if flags & LONG_TRACE_ID != 0 { 16 } else { 8 };
+        let parent_id_len = if flags & PARENT_ID != 0 { 8 } else { 0 };
+        if rest.len() != trace_id_len + 8 + parent_id_len {
+            return None;
+        }
+
+        let (trace_id, rest) = rest.split_at(trace_id_len);
+        let trace_id = match trace_id_len {
+            16 => TraceId::from(<[u8; 16]>::try_from(trace_id).ok()?),
+            _ => TraceId::from(<[u8; 8]>::try_from(trace_id).ok()?),
+        };
+        let (span_id, parent_id) = rest.split_at(8);
+
+        let mut builder = TraceContext::builder();
+        builder
+            .trace_id(trace_id)
+            .span_id(SpanId::from(<[u8; 8]>::try_from(span_id).ok()?))
+            .debug(flags & DEBUG != 0);
+        if !parent_id.is_empty() {
+            builder.parent_id(SpanId::from(<[u8; 8]>::try_from(parent_id).ok()?));
+        }
+        if flags & SAMPLING_DECIDED != 0 {
+            builder.sampled(flags & SAMPLED != 0);
+        }
+
+        Some(builder.build())
+    }
 }
 
+const LONG_TRACE_ID: u8 = 1 << 0;
+const PARENT_ID: u8 = 1 << 1;
+const SAMPLING_DECIDED: u8 = 1 << 2;
+const SAMPLED: u8 = 1 << 3;
+const DEBUG: u8 = 1 << 4;
+
+/// Formats the context in the format of the `b3` single header.
+///
+/// The format is `{trace_id}-{span_id}-{sampling_state}-{parent_id}`, where the sampling state and parent ID are
+/// omitted if not present. The sampling state is `d` for debug contexts, and `1` or `0` otherwise.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}-{}", self.trace_id, self.span_id)?;
+        if self.debug() {
+            fmt.write_str("-d")?;
+        } else if self.sampled() == Some(true) {
+            fmt.write_str("-1")?;
+        } else if self.sampled() == Some(false) {
+            fmt.write_str("-0")?;
+        }
+        if let Some(parent_id) = self.parent_id {
+            write!(fmt, "-{}", parent_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a context from the format of the `b3` single header.
+impl FromStr for TraceContext {
+    type Err = TraceContextParseError;
+
+    fn from_str(s: &str) -> Result<TraceContext, TraceContextParseError> {
+        let mut parts = s.split('-');
+
+        let trace_id = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or(TraceContextParseError(()))?;
+        let span_id = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or(TraceContextParseError(()))?;
+
+        let mut builder = TraceContext::builder();
+        builder.trace_id(trace_id).span_id(span_id);
+
+        let maybe_sampling = match parts.next() {
+            Some(next) => next,
+            None => return Ok(builder.build()),
+        };
+
+        let parent_id = if maybe_sampling == "d" {
+            builder.debug(true);
+            parts.next()
+        } else if maybe_sampling == "1" {
+            builder.sampled(true);
+            parts.next()
+        } else if maybe_sampling == "0" {
+            builder.sampled(false);
+            parts.next()
+        } else {
+            Some(maybe_sampling)
+        };
+
+        if let Some(parent_id) = parent_id {
+            let parent_id = parent_id.parse().map_err(|_| TraceContextParseError(()))?;
+            builder.parent_id(parent_id);
+        }
+
+        if parts.next().is_some() {
+            return Err(TraceContextParseError(()));
+        }
+
+        Ok(builder.build())
+    }
+}
+
+/// Creates a span builder with the trace ID, span ID, and parent ID of a context.
+///
+/// The span's debug flag is set if the context is in debug mode. This is useful to synthesize spans from timing data
+/// recorded outside of an `OpenSpan`.
+impl From<TraceContext> for span::Builder {
+    #[inline]
+    fn from(c: TraceContext) -> span::Builder {
+        let mut builder = Span::builder();
+        builder.trace_id(c.trace_id).id(c.span_id).debug(c.debug());
+        if let Some(parent_id) = c.parent_id {
+            builder.parent_id(parent_id);
+        }
+        builder
+    }
+}
+
+/// The error returned when parsing a `TraceContext` from a string.
+#[derive(Debug)]
+pub struct TraceContextParseError(());
+
+impl fmt::Display for TraceContextParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("error parsing trace context")
+    }
+}
+
+impl Error for TraceContextParseError {}
+
 /// A builder type for `TraceContext`s.
 pub struct Builder {
     trace_id: Option<TraceId>,
@@ -13,16 +13,18 @@
 //  limitations under the License.
 
 //! Tracers.
+use crate::clock::SystemClock;
+use crate::sample::SamplingPolicy;
 use crate::trace_context;
 use crate::{
-    Attached, Endpoint, OpenSpan, Report, Sample, SamplingFlags, Span, SpanId, SpanState,
-    TraceContext, TraceId,
+    Attached, Clock, Detached, Endpoint, Kind, OpenSpan, Report, Sample, SamplingFlags, Span,
+    SpanId, SpanState, TagKeyPolicy, TraceContext, TraceId,
 };
 use lazycell::AtomicLazyCell;
 use rand::Rng;
 use std::error::Error;
 use std::fmt;
-use std::time::{Instant, SystemTime};
+use std::time::{Duration, SystemTime};
 
 pub(crate) static TRACER: AtomicLazyCell<Tracer> = AtomicLazyCell::NONE;
 
@@ -30,6 +32,58 @@ pub(crate) struct Tracer {
     pub sampler: Box<dyn Sample + Sync + Send>,
     pub reporter: Box<dyn Report + Sync + Send>,
     pub local_endpoint: Endpoint,
+    pub clock: Box<dyn Clock + Sync + Send>,
+    pub tag_key_policy: TagKeyPolicy,
+    pub min_duration: Duration,
+    pub name_policy: NamePolicy,
+    pub sampling_policy: Option<Box<dyn SamplingPolicy + Sync + Send>>,
+}
+
+/// The normalization applied to the names of spans.
+#[derive(Copy, Clone)]
+#[non_exhaustive]
+pub enum NamePolicy {
+    /// The name is used as-is.
+    None,
+
+    /// The name is converted to lowercase.
+    Lowercase,
+
+    /// The name is transformed by a custom function.
+    Custom(fn(&str) -> String),
+}
+
+impl fmt::Debug for NamePolicy {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamePolicy::None => fmt.write_str("None"),
+            NamePolicy::Lowercase => fmt.write_str("Lowercase"),
+            NamePolicy::Custom(f) => fmt.debug_tuple("Custom").field(&(*f as *const ())).finish(),
+        }
+    }
+}
+
+impl Default for NamePolicy {
+    #[inline]
+    fn default() -> NamePolicy {
+        NamePolicy::None
+    }
+}
+
+impl NamePolicy {
+    pub(crate) fn apply(&self, name: String) -> String {
+        match self {
+            NamePolicy::None => name,
+            NamePolicy::Lowercase => {
+                if name.chars().any(char::is_uppercase) {
+                    name.to_lowercase()
+                } else {
+                    name
+                }
+            }
+            NamePolicy::Custom(f) => f(&name),
+        }
+    }
 }
 
 /// Initializes the global tracer.
@@ -47,13 +101,97 @@ where
     S: Sample + 'static + Sync + Send,
     R: Report + 'static + Sync + Send,
 {
-    TRACER
-        .fill(Tracer {
-            sampler: Box::new(sampler),
-            reporter: Box::new(reporter),
-            local_endpoint,
-        })
-        .map_err(|_| SetTracerError(()))
+    TracerBuilder::new(sampler, reporter, local_endpoint).init()
+}
+
+/// A builder used to configure and initialize the global tracer.
+///
+/// `set_tracer` is a shorthand for initializing a tracer with the default configuration.
+pub struct TracerBuilder {
+    tracer: Tracer,
+}
+
+impl TracerBuilder {
+    /// Creates a new builder with the required components of a tracer.
+    pub fn new<S, R>(sampler: S, reporter: R, local_endpoint: Endpoint) -> TracerBuilder
+    where
+        S: Sample + 'static + Sync + Send,
+        R: Report + 'static + Sync + Send,
+    {
+        TracerBuilder {
+            tracer: Tracer {
+                sampler: Box::new(sampler),
+                reporter: Box::new(reporter),
+                local_endpoint,
+                clock: Box::new(SystemClock),
+                tag_key_policy: TagKeyPolicy::default(),
+                min_duration: Duration::from_secs(0),
+                name_policy: NamePolicy::default(),
+                sampling_policy: None,
+            },
+        }
+    }
+
+    /// Sets the clock used to compute the durations of spans.
+    ///
+    /// Defaults to `SystemClock`.
+    pub fn clock<C>(mut self, clock: C) -> TracerBuilder
+    where
+        C: Clock + 'static + Sync + Send,
+    {
+        self.tracer.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the handling of tag keys containing control characters.
+    ///
+    /// Defaults to `TagKeyPolicy::Sanitize`.
+    pub fn tag_key_policy(mut self, tag_key_policy: TagKeyPolicy) -> TracerBuilder {
+        self.tracer.tag_key_policy = tag_key_policy;
+        self
+    }
+
+    /// Sets the minimum duration of reported spans.
+    ///
+    /// Spans which finish in less time are dropped rather than reported, unless they are in debug mode. The threshold
+    /// is compared against the measured duration, before it is rounded up to the 1 microsecond minimum of the Zipkin
+    /// wire format, so a span which would be reported with a duration of 1 microsecond may still be dropped by a
+    /// threshold of 1 microsecond.
+    ///
+    /// Defaults to 0, which reports all spans.
+    pub fn min_duration(mut self, min_duration: Duration) -> TracerBuilder {
+        self.tracer.min_duration = min_duration;
+        self
+    }
+
+    /// Sets the normalization applied to the names of spans.
+    ///
+    /// Defaults to `NamePolicy::None`.
+    pub fn name_policy(mut self, name_policy: NamePolicy) -> TracerBuilder {
+        self.tracer.name_policy = name_policy;
+        self
+    }
+
+    /// Sets a policy which can override the sampling decisions of joined trace contexts.
+    ///
+    /// Defaults to no policy.
+    pub fn sampling_policy<P>(mut self, sampling_policy: P) -> TracerBuilder
+    where
+        P: SamplingPolicy + 'static + Sync + Send,
+    {
+        self.tracer.sampling_policy = Some(Box::new(sampling_policy));
+        self
+    }
+
+    /// Initializes the global tracer.
+    ///
+    /// The tracer can only be initialized once in the lifetime of a program. Spans created before this method is
+    /// called will be no-ops.
+    ///
+    /// Returns an error if the tracer is already initialized.
+    pub fn init(self) -> Result<(), SetTracerError> {
+        TRACER.fill(self.tracer).map_err(|_| SetTracerError(()))
+    }
 }
 
 /// The error returned when attempting to set a tracer when one is already installed.
@@ -68,39 +206,95 @@ impl fmt::Display for SetTracerError {
 
 impl Error for SetTracerError {}
 
+/// Determines if the global tracer has been initialized.
+///
+/// If this returns `false`, all spans are no-ops. It can be used to skip setup which is only needed when tracing.
+pub fn tracer_installed() -> bool {
+    TRACER.borrow().is_some()
+}
+
+/// Determines if the current span is being recorded.
+///
+/// This returns `true` only if a tracer is installed and the thread's current trace context is sampled. It can be
+/// used to skip expensive work like computing the value of a tag when it would be discarded anyway.
+pub fn is_recording() -> bool {
+    tracer_installed() && matches!(crate::current(), Some(c) if c.sampled() == Some(true))
+}
+
 /// Starts a new trace.
+///
+/// Unlike `next_span`, this ignores the thread's current trace context, so the span will never have a parent.
 pub fn new_trace() -> OpenSpan<Attached> {
     new_trace_from(SamplingFlags::default())
 }
 
 /// Stats a new trace with specific sampling flags.
 pub fn new_trace_from(flags: SamplingFlags) -> OpenSpan<Attached> {
+    make_span(root_context(flags), false, None)
+}
+
+fn root_context(flags: SamplingFlags) -> TraceContext {
     let id = next_id();
-    let context = TraceContext::builder()
+    TraceContext::builder()
         .trace_id(TraceId::from(id))
         .span_id(SpanId::from(id))
         .sampling_flags(flags)
-        .build();
-    make_span(context, false)
+        .build()
 }
 
 /// Joins an existing trace.
 ///
-/// The context can come from, for example, the headers of an HTTP request.
+/// The context can come from, for example, the headers of an HTTP request. If the tracer has a `SamplingPolicy`, it
+/// may override the context's sampling decision unless the context is in debug mode.
 pub fn join_trace(context: TraceContext) -> OpenSpan<Attached> {
-    make_span(context, true)
+    let (context, shared) = apply_sampling_policy(context);
+    make_span(context, shared, None)
+}
+
+fn apply_sampling_policy(context: TraceContext) -> (TraceContext, bool) {
+    // debug contexts are always sampled, so the policy can't override them
+    if context.debug() {
+        return (context, true);
+    }
+
+    let decision = TRACER
+        .borrow()
+        .and_then(|tracer| tracer.sampling_policy.as_ref())
+        .and_then(|policy| policy.decide(&context));
+
+    match decision {
+        // the remote service didn't record the span if it didn't decide to sample it, so we can't share it
+        Some(sampled) if context.sampled() != Some(sampled) => {
+            let context = trace_context::Builder::from(context)
+                .sampled(sampled)
+                .build();
+            (context, false)
+        }
+        _ => (context, true),
+    }
 }
 
 /// Stats a new span with the specified parent.
 pub fn new_child(parent: TraceContext) -> OpenSpan<Attached> {
+    make_span(child_context(parent), false, None)
+}
+
+/// Starts a new detached span with the specified parent.
+///
+/// Unlike `new_child(parent).detach()`, this never reads or modifies the thread's current trace context.
+pub fn new_child_detached(parent: TraceContext) -> OpenSpan<Detached> {
+    let (context, state) = start_span(child_context(parent), false, None);
+    OpenSpan::new_detached(context, state)
+}
+
+pub(crate) fn child_context(parent: TraceContext) -> TraceContext {
     let id = next_id();
-    let context = TraceContext::builder()
+    TraceContext::builder()
         .trace_id(parent.trace_id())
         .parent_id(parent.span_id())
         .span_id(SpanId::from(id))
         .sampling_flags(parent.sampling_flags())
-        .build();
-    make_span(context, false)
+        .build()
 }
 
 /// Creates a new span parented to the current one if it exists, or starting a new trace otherwise.
@@ -111,47 +305,116 @@ pub fn next_span() -> OpenSpan<Attached> {
     }
 }
 
+/// Like `next_span`, but sets the name of the span as it is created.
+///
+/// Unlike `next_span().with_name(name)`, the name is available to the tracer's sampler when deciding if a new trace
+/// should be sampled.
+pub fn next_span_named(name: &str) -> OpenSpan<Attached> {
+    let context = match crate::current() {
+        Some(context) => child_context(context),
+        None => root_context(SamplingFlags::default()),
+    };
+    make_span(context, false, Some(name))
+}
+
+/// Like `next_span`, but the span is recorded regardless of the sampling decision of the current trace.
+///
+/// The span is created in debug mode, and its debug flag propagates to spans created within it. The rest of the trace
+/// is unaffected, so if the trace is unsampled, only this span and its descendants are recorded, and the trace will
+/// be incomplete when viewed in Zipkin.
+pub fn next_span_forced() -> OpenSpan<Attached> {
+    let flags = SamplingFlags::builder().debug(true).build();
+    let context = match crate::current() {
+        Some(context) => trace_context::Builder::from(child_context(context))
+            .sampling_flags(flags)
+            .build(),
+        None => root_context(flags),
+    };
+    make_span(context, false, None).with_debug(true)
+}
+
+/// Starts the client half of an RPC.
+///
+/// This is a `next_span` of kind `Client` named after the RPC. The span's context should be sent along with the
+/// request so the server can pass it to `rpc_server_span`.
+pub fn rpc_client_span(name: &str) -> OpenSpan<Attached> {
+    next_span_named(name).with_kind(Kind::Client)
+}
+
+/// Starts the server half of an RPC.
+///
+/// This joins the trace of a context received from the client like `join_trace`, creating a span of kind `Server`
+/// named after the RPC. The span shares its ID with the client's span.
+pub fn rpc_server_span(context: TraceContext, name: &str) -> OpenSpan<Attached> {
+    let (context, shared) = apply_sampling_policy(context);
+    make_span(context, shared, Some(name)).with_kind(Kind::Server)
+}
+
 fn next_id() -> [u8; 8] {
     let mut id = [0; 8];
     rand::thread_rng().fill(&mut id);
     id
 }
 
-fn make_span(mut context: TraceContext, mut shared: bool) -> OpenSpan<Attached> {
+fn make_span(context: TraceContext, shared: bool, name: Option<&str>) -> OpenSpan<Attached> {
+    let (context, state) = start_span(context, shared, name);
+    OpenSpan::new(context, state)
+}
+
+fn start_span(
+    mut context: TraceContext,
+    mut shared: bool,
+    name: Option<&str>,
+) -> (TraceContext, SpanState) {
+    // unsampled spans are common in hot code, so bail out before doing any other work
+    if context.sampled() == Some(false) {
+        return (context, SpanState::Nop);
+    }
+
     let tracer = match TRACER.borrow() {
         Some(tracer) => tracer,
-        None => return OpenSpan::new(context, SpanState::Nop),
+        None => return (context, SpanState::Nop),
     };
 
-    if context.sampled().is_none() {
-        context = trace_context::Builder::from(context)
-            .sampled(tracer.sampler.sample(context.trace_id()))
-            .build();
-        // since the thing we got the context from didn't indicate if it should be sampled,
-        // we can't assume they're recording the span as well.
-        shared = false;
-    }
-
-    let state = match context.sampled() {
-        Some(false) => SpanState::Nop,
-        _ => {
-            let mut span = Span::builder();
-            span.trace_id(context.trace_id())
-                .id(context.span_id())
-                .timestamp(SystemTime::now())
-                .shared(shared)
-                .local_endpoint(tracer.local_endpoint.clone());
-
-            if let Some(parent_id) = context.parent_id() {
-                span.parent_id(parent_id);
-            }
-
-            SpanState::Real {
-                span,
-                start_instant: Instant::now(),
-            }
+    let sampled = match context.sampled() {
+        Some(sampled) => sampled,
+        None => {
+            let sampled = tracer.sampler.sample_context(&context, name);
+            context = trace_context::Builder::from(context)
+                .sampled(sampled)
+                .build();
+            // since the thing we got the context from didn't indicate if it should be sampled,
+            // we can't assume they're recording the span as well.
+            shared = false;
+            sampled
         }
     };
 
-    OpenSpan::new(context, state)
+    // nothing about the span needs to be set up if it isn't going to be recorded
+    if !sampled {
+        return (context, SpanState::Nop);
+    }
+
+    let mut span = Span::builder();
+    span.trace_id(context.trace_id())
+        .id(context.span_id())
+        .timestamp(SystemTime::now())
+        .shared(shared)
+        .local_endpoint(tracer.local_endpoint.clone())
+        .tag_key_policy(tracer.tag_key_policy);
+
+    if let Some(parent_id) = context.parent_id() {
+        span.parent_id(parent_id);
+    }
+
+    if let Some(name) = name {
+        span.name(tracer.name_policy.apply(name.to_string()));
+    }
+
+    let state = SpanState::Real {
+        span,
+        start_instant: tracer.clock.now_instant(),
+    };
+
+    (context, state)
 }
@@ -0,0 +1,38 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+use std::mem;
+use std::sync::Mutex;
+use zipkin::sample::Sample;
+use zipkin::{Endpoint, Report, Span, TracerBuilder};
+
+static SPANS: Mutex<Vec<Span>> = Mutex::new(vec![]);
+
+struct TestReporter;
+
+impl Report for TestReporter {
+    fn report(&self, span: Span) {
+        SPANS.lock().unwrap().push(span);
+    }
+}
+
+pub fn builder<S>(sampler: S) -> TracerBuilder
+where
+    S: Sample + 'static + Sync + Send,
+{
+    TracerBuilder::new(sampler, TestReporter, Endpoint::builder().build())
+}
+
+pub fn take() -> Vec<Span> {
+    mem::take(&mut *SPANS.lock().unwrap())
+}
@@ -0,0 +1,47 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+mod common;
+
+use zipkin::sample::NeverSampler;
+
+#[test]
+fn next_span_forced() {
+    common::builder(NeverSampler).init().unwrap();
+
+    let root = zipkin::next_span().with_name("root");
+    assert!(!root.is_recording());
+
+    let forced = zipkin::next_span_forced().with_name("forced");
+    assert!(forced.is_recording());
+    assert_eq!(forced.context().trace_id(), root.context().trace_id());
+    assert_eq!(forced.context().parent_id(), Some(root.context().span_id()));
+
+    let child = zipkin::next_span().with_name("child");
+    assert!(child.is_recording());
+    drop(child);
+    drop(forced);
+
+    zipkin::next_span().with_name("unsampled");
+    drop(root);
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("child"));
+    assert_eq!(spans[1].name(), Some("forced"));
+    assert!(spans[1].debug());
+
+    let root = zipkin::next_span_forced();
+    assert!(root.is_recording());
+    assert_eq!(root.context().parent_id(), None);
+}
@@ -0,0 +1,33 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+mod common;
+
+use zipkin::sample::AlwaysSampler;
+use zipkin::NamePolicy;
+
+#[test]
+fn lowercase_names() {
+    common::builder(AlwaysSampler)
+        .name_policy(NamePolicy::Lowercase)
+        .init()
+        .unwrap();
+
+    zipkin::next_span_named("Named");
+    zipkin::next_span().with_name("GET /Foo");
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("named"));
+    assert_eq!(spans[1].name(), Some("get /foo"));
+}
@@ -0,0 +1,37 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+mod common;
+
+use std::time::Duration;
+use zipkin::sample::AlwaysSampler;
+use zipkin::SamplingFlags;
+
+#[test]
+fn min_duration() {
+    common::builder(AlwaysSampler)
+        .min_duration(Duration::from_secs(60))
+        .init()
+        .unwrap();
+
+    zipkin::next_span().with_name("short");
+    assert!(common::take().is_empty());
+
+    zipkin::next_span().with_name("debug").with_debug(true);
+    zipkin::new_trace_from(SamplingFlags::builder().debug(true).build()).with_name("debug context");
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].name(), Some("debug"));
+    assert_eq!(spans[1].name(), Some("debug context"));
+}
@@ -0,0 +1,31 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+
+#[test]
+fn is_recording_without_tracer() {
+    assert!(!zipkin::is_recording());
+
+    let span = zipkin::new_trace();
+    assert!(!span.is_recording());
+    assert!(!zipkin::is_recording());
+
+    let span = zipkin::new_trace_from(zipkin::SamplingFlags::builder().sampled(true).build());
+    assert!(!span.is_recording());
+    assert!(!zipkin::is_recording());
+}
+
+#[test]
+fn tracer_not_installed() {
+    assert!(!zipkin::tracer_installed());
+}
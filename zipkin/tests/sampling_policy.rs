@@ -0,0 +1,100 @@
+//  Copyright 2020 Palantir Technologies, Inc.
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+mod common;
+
+use zipkin::sample::NeverSampler;
+use zipkin::TraceContext;
+
+// samples contexts whose parent is the span with ID 1 and drops those whose parent is the span with ID 5, as if a
+// sidecar had requested it
+fn policy(context: &TraceContext) -> Option<bool> {
+    if context.parent_id() == Some([1; 8].into()) {
+        Some(true)
+    } else if context.parent_id() == Some([5; 8].into()) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn sampling_policy() {
+    common::builder(NeverSampler)
+        .sampling_policy(policy)
+        .init()
+        .unwrap();
+
+    let forced = TraceContext::builder()
+        .trace_id([0; 8].into())
+        .parent_id([1; 8].into())
+        .span_id([2; 8].into())
+        .build();
+    let span = zipkin::join_trace(forced);
+    assert!(span.is_recording());
+    assert_eq!(span.context().sampled(), Some(true));
+    drop(span);
+
+    let unforced = TraceContext::builder()
+        .trace_id([0; 8].into())
+        .parent_id([3; 8].into())
+        .span_id([4; 8].into())
+        .build();
+    assert!(!zipkin::join_trace(unforced).is_recording());
+    assert!(!zipkin::next_span().is_recording());
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), [2; 8].into());
+    assert!(!spans[0].shared());
+
+    let forced = TraceContext::builder()
+        .trace_id([1; 8].into())
+        .parent_id([1; 8].into())
+        .span_id([2; 8].into())
+        .build();
+    let span = zipkin::rpc_server_span(forced, "get");
+    assert!(span.is_recording());
+    assert_eq!(span.context().sampled(), Some(true));
+    drop(span);
+
+    let dropped = TraceContext::builder()
+        .trace_id([1; 8].into())
+        .parent_id([5; 8].into())
+        .span_id([6; 8].into())
+        .sampled(true)
+        .build();
+    assert!(!zipkin::rpc_server_span(dropped, "get").is_recording());
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), [2; 8].into());
+    assert!(!spans[0].shared());
+
+    // the policy can't override debug contexts
+    let debug = TraceContext::builder()
+        .trace_id([2; 8].into())
+        .parent_id([5; 8].into())
+        .span_id([6; 8].into())
+        .debug(true)
+        .build();
+    let span = zipkin::join_trace(debug);
+    assert!(span.is_recording());
+    assert_eq!(span.context().sampled(), Some(true));
+    drop(span);
+
+    let spans = common::take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id(), [6; 8].into());
+    assert!(spans[0].shared());
+}